@@ -0,0 +1,885 @@
+//! RFC 5234 ABNF front end and printer.
+//!
+//! ABNF is parsed into a small syntax tree and then desugared into the BNF
+//! model. Repetitions, options and groups of alternatives become fresh
+//! nonterminals named after the rule they appear in, as do value ranges
+//! such as `%x41-5A`. Case-insensitive strings are spelled out letter by
+//! letter through shared nonterminals such as `<g-or-G>`. Prose values
+//! (`<some prose>`) become references to a nonterminal of the same name.
+
+use error::{Error, ParseError};
+use expression::Expression;
+use fresh::FreshNames;
+use grammar::Grammar;
+use production::Production;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use term::Term;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_till, take_until, take_while, take_while1},
+    character::complete::{self, digit0, digit1, line_ending, one_of, space1},
+    combinator::{all_consuming, map, map_res, opt, recognize},
+    error::{VerboseError, VerboseErrorKind},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// RFC 5234 Appendix B.1 core rules
+const CORE_RULES: &str = "\
+ALPHA = %x41-5A / %x61-7A
+BIT = \"0\" / \"1\"
+CHAR = %x01-7F
+CR = %x0D
+CRLF = CR LF
+CTL = %x00-1F / %x7F
+DIGIT = %x30-39
+DQUOTE = %x22
+HEXDIG = DIGIT / \"A\" / \"B\" / \"C\" / \"D\" / \"E\" / \"F\"
+HTAB = %x09
+LF = %x0A
+LWSP = *(WSP / CRLF WSP)
+OCTET = %x00-FF
+SP = %x20
+VCHAR = %x21-7E
+WSP = SP / HTAB
+";
+
+/// Largest value range which will be expanded into alternatives
+const MAX_RANGE: u32 = 0x1_0000;
+
+type Alternation = Vec<Concatenation>;
+type Concatenation = Vec<Repetition>;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Element {
+    Rule(String),
+    Group(Alternation),
+    Option(Alternation),
+    Text { text: String, case_sensitive: bool },
+    Range(u32, u32),
+    Values(Vec<u32>),
+    Prose(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Repetition {
+    min: usize,
+    max: Option<usize>,
+    element: Element,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    name: String,
+    incremental: bool,
+    alternation: Alternation,
+}
+
+fn comment(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    recognize(pair(
+        complete::char(';'),
+        take_till(|c| c == '\r' || c == '\n'),
+    ))(input)
+}
+
+fn eof(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    if input.is_empty() {
+        Ok((input, input))
+    } else {
+        Err(nom::Err::Error(nom::error::ParseError::from_error_kind(
+            input,
+            nom::error::ErrorKind::Eof,
+        )))
+    }
+}
+
+/// A comment and/or line ending
+fn c_nl(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((
+        recognize(pair(comment, alt((line_ending, eof)))),
+        line_ending,
+    ))(input)
+}
+
+/// Whitespace, possibly continuing onto an indented line
+fn c_wsp(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((space1, recognize(pair(c_nl, space1))))(input)
+}
+
+fn rulename(input: &str) -> IResult<&str, String, VerboseError<&str>> {
+    let (input, name) = recognize(pair(
+        take_while1(|c: char| c.is_ascii_alphabetic()),
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '-'),
+    ))(input)?;
+
+    Ok((input, name.to_string()))
+}
+
+fn number(base: u32) -> impl Fn(&str) -> IResult<&str, u32, VerboseError<&str>> {
+    move |input| {
+        map_res(take_while1(move |c: char| c.is_digit(base)), move |s| {
+            u32::from_str_radix(s, base)
+        })(input)
+    }
+}
+
+/// Reject the text at `input` outright, as no alternative could accept it
+fn invalid<'a, O>(
+    input: &'a str,
+    message: &'static str,
+) -> IResult<&'a str, O, VerboseError<&'a str>> {
+    Err(nom::Err::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(message))],
+    }))
+}
+
+fn num_val(input: &str) -> IResult<&str, Element, VerboseError<&str>> {
+    let start = input;
+    let (input, base) = preceded(complete::char('%'), one_of("bdxBDX"))(input)?;
+    let base = match base.to_ascii_lowercase() {
+        'b' => 2,
+        'd' => 10,
+        _ => 16,
+    };
+
+    let (input, first) = number(base)(input)?;
+    if let Ok((input, last)) = preceded(complete::char('-'), number(base))(input) {
+        if last < first || last - first >= MAX_RANGE {
+            return invalid(
+                start,
+                "unsupported value range, expected at most 65536 values",
+            );
+        }
+        return Ok((input, Element::Range(first, last)));
+    }
+
+    let (input, mut rest) = many0(preceded(complete::char('.'), number(base)))(input)?;
+    rest.insert(0, first);
+    if rest
+        .iter()
+        .any(|&value| std::char::from_u32(value).is_none())
+    {
+        return invalid(start, "expected values which are valid characters");
+    }
+
+    Ok((input, Element::Values(rest)))
+}
+
+fn char_val(input: &str) -> IResult<&str, Element, VerboseError<&str>> {
+    let (input, sensitivity) = opt(alt((tag_no_case("%s"), tag_no_case("%i"))))(input)?;
+    let (input, text) = delimited(
+        complete::char('"'),
+        take_till(|c| c == '"' || c == '\r' || c == '\n'),
+        complete::char('"'),
+    )(input)?;
+
+    let case_sensitive = sensitivity.is_some_and(|s| s.eq_ignore_ascii_case("%s"));
+    Ok((
+        input,
+        Element::Text {
+            text: text.to_string(),
+            case_sensitive,
+        },
+    ))
+}
+
+fn prose_val(input: &str) -> IResult<&str, Element, VerboseError<&str>> {
+    let (input, prose) =
+        delimited(complete::char('<'), take_until(">"), complete::char('>'))(input)?;
+
+    Ok((input, Element::Prose(prose.to_string())))
+}
+
+fn element(input: &str) -> IResult<&str, Element, VerboseError<&str>> {
+    alt((
+        map(rulename, Element::Rule),
+        map(
+            delimited(
+                pair(complete::char('('), many0(c_wsp)),
+                alternation,
+                pair(many0(c_wsp), complete::char(')')),
+            ),
+            Element::Group,
+        ),
+        map(
+            delimited(
+                pair(complete::char('['), many0(c_wsp)),
+                alternation,
+                pair(many0(c_wsp), complete::char(']')),
+            ),
+            Element::Option,
+        ),
+        char_val,
+        num_val,
+        prose_val,
+    ))(input)
+}
+
+fn parse_count(digits: &str) -> Option<usize> {
+    digits.parse().ok()
+}
+
+fn repetition(input: &str) -> IResult<&str, Repetition, VerboseError<&str>> {
+    let (input, repeat) = opt(alt((
+        map(
+            tuple((digit0, complete::char('*'), digit0)),
+            |(min, _, max): (&str, char, &str)| (parse_count(min).unwrap_or(0), parse_count(max)),
+        ),
+        map_res(digit1, |count: &str| {
+            count.parse::<usize>().map(|count| (count, Some(count)))
+        }),
+    )))(input)?;
+    let (input, element) = element(input)?;
+    let (min, max) = repeat.unwrap_or((1, Some(1)));
+
+    Ok((input, Repetition { min, max, element }))
+}
+
+fn concatenation(input: &str) -> IResult<&str, Concatenation, VerboseError<&str>> {
+    let (input, (first, mut rest)) =
+        pair(repetition, many0(preceded(many1(c_wsp), repetition)))(input)?;
+    rest.insert(0, first);
+
+    Ok((input, rest))
+}
+
+fn alternation(input: &str) -> IResult<&str, Alternation, VerboseError<&str>> {
+    let (input, (first, mut rest)) = pair(
+        concatenation,
+        many0(preceded(
+            tuple((many0(c_wsp), complete::char('/'), many0(c_wsp))),
+            concatenation,
+        )),
+    )(input)?;
+    rest.insert(0, first);
+
+    Ok((input, rest))
+}
+
+fn rule(input: &str) -> IResult<&str, Rule, VerboseError<&str>> {
+    let (input, (name, defined_as, alternation, _)) = tuple((
+        rulename,
+        delimited(many0(c_wsp), alt((tag("=/"), tag("="))), many0(c_wsp)),
+        alternation,
+        pair(many0(c_wsp), alt((c_nl, eof))),
+    ))(input)?;
+
+    Ok((
+        input,
+        Rule {
+            name,
+            incremental: defined_as == "=/",
+            alternation,
+        },
+    ))
+}
+
+fn rulelist(input: &str) -> IResult<&str, Vec<Rule>, VerboseError<&str>> {
+    let (input, rules) = all_consuming(terminated(
+        many1(alt((
+            map(rule, Some),
+            map(pair(many0(c_wsp), c_nl), |_| None),
+        ))),
+        many0(c_wsp),
+    ))(input)?;
+
+    Ok((input, rules.into_iter().flatten().collect()))
+}
+
+fn parse_rules(input: &str) -> Result<Vec<Rule>, Error> {
+    match rulelist(input) {
+        Result::Ok((_, rules)) => Ok(rules),
+        Result::Err(e) => Err(Error::from(ParseError::from_nom(input, e))),
+    }
+}
+
+fn core_rules() -> Vec<Rule> {
+    parse_rules(CORE_RULES).expect("core rules should parse")
+}
+
+fn references(alternation: &[Concatenation], into: &mut Vec<String>) {
+    for repetition in alternation.iter().flatten() {
+        match repetition.element {
+            Element::Rule(ref name) => into.push(name.clone()),
+            Element::Group(ref inner) | Element::Option(ref inner) => references(inner, into),
+            _ => (),
+        }
+    }
+}
+
+/// Like `references`, but also collects the names in prose values, which
+/// lower to nonterminals of the same name
+fn names(alternation: &[Concatenation], into: &mut Vec<String>) {
+    for repetition in alternation.iter().flatten() {
+        match repetition.element {
+            Element::Rule(ref name) | Element::Prose(ref name) => into.push(name.clone()),
+            Element::Group(ref inner) | Element::Option(ref inner) => names(inner, into),
+            _ => (),
+        }
+    }
+}
+
+/// Lowers ABNF rules into `Production`s
+struct Desugar {
+    names: FreshNames,
+    /// canonical spelling of each rule name, keyed by its lowercase form
+    canonical: HashMap<String, String>,
+    letters: HashMap<char, Term>,
+    productions: Vec<Production>,
+}
+
+impl Desugar {
+    fn expressions(&mut self, rule: &str, alternation: &[Concatenation]) -> Vec<Expression> {
+        alternation
+            .iter()
+            .map(|concatenation| self.expression(rule, concatenation))
+            .collect()
+    }
+
+    fn expression(&mut self, rule: &str, concatenation: &[Repetition]) -> Expression {
+        let mut terms = vec![];
+        for repetition in concatenation {
+            terms.append(&mut self.repetition(rule, repetition));
+        }
+
+        terms.retain(|term| *term != Term::Terminal(String::new()));
+        if terms.is_empty() {
+            terms.push(Term::Terminal(String::new()));
+        }
+        Expression::from_parts(terms)
+    }
+
+    fn repetition(&mut self, rule: &str, repetition: &Repetition) -> Vec<Term> {
+        let element = self.element(rule, &repetition.element);
+        let mut terms = vec![];
+        for _ in 0..repetition.min {
+            terms.extend(element.iter().cloned());
+        }
+
+        let empty = || Expression::from_parts(vec![Term::Terminal(String::new())]);
+        match repetition.max {
+            None => {
+                // *element
+                let name = self.names.fresh(&format!("{}-rep", rule));
+                let repeat = Term::Nonterminal(name.clone());
+                let mut more = element.clone();
+                more.push(repeat.clone());
+                terms.push(self.define(name, vec![empty(), Expression::from_parts(more)]));
+            }
+            Some(max) if max > repetition.min => {
+                // each optional element nests the ones after it
+                let mut tail: Option<Term> = None;
+                for _ in repetition.min..max {
+                    let name = self.names.fresh(&format!("{}-opt", rule));
+                    let mut more = element.clone();
+                    more.extend(tail.take());
+                    tail = Some(self.define(name, vec![empty(), Expression::from_parts(more)]));
+                }
+                terms.extend(tail);
+            }
+            Some(_) => (),
+        }
+
+        terms
+    }
+
+    fn element(&mut self, rule: &str, element: &Element) -> Vec<Term> {
+        match *element {
+            Element::Rule(ref name) => vec![Term::Nonterminal(self.canonical(name))],
+            Element::Prose(ref prose) => vec![Term::Nonterminal(prose.clone())],
+            Element::Group(ref alternation) if alternation.len() == 1 => self
+                .expression(rule, &alternation[0])
+                .terms_iter()
+                .cloned()
+                .collect(),
+            Element::Group(ref alternation) => {
+                let name = self.names.fresh(&format!("{}-group", rule));
+                let rhs = self.expressions(rule, alternation);
+                vec![self.define(name, rhs)]
+            }
+            Element::Option(ref alternation) => {
+                let name = self.names.fresh(&format!("{}-opt", rule));
+                let mut rhs = self.expressions(rule, alternation);
+                rhs.push(Expression::from_parts(vec![Term::Terminal(String::new())]));
+                vec![self.define(name, rhs)]
+            }
+            Element::Text {
+                ref text,
+                case_sensitive,
+            } => {
+                if case_sensitive {
+                    vec![Term::Terminal(text.clone())]
+                } else {
+                    self.case_insensitive(text)
+                }
+            }
+            Element::Values(ref values) => {
+                let text = values
+                    .iter()
+                    .filter_map(|&value| std::char::from_u32(value))
+                    .collect();
+                vec![Term::Terminal(text)]
+            }
+            Element::Range(first, last) => {
+                let mut rhs = vec![];
+                for value in first..=last {
+                    // surrogates are not characters, and cannot be matched
+                    if let Some(c) = std::char::from_u32(value) {
+                        rhs.push(Expression::from_parts(vec![Term::Terminal(c.to_string())]));
+                    }
+                }
+                let name = self.names.fresh(&format!("{}-range", rule));
+                vec![self.define(name, rhs)]
+            }
+        }
+    }
+
+    /// Split text into runs of non-letters and shared per-letter alternatives
+    fn case_insensitive(&mut self, text: &str) -> Vec<Term> {
+        let mut terms = vec![];
+        let mut run = String::new();
+        for c in text.chars() {
+            if !c.is_ascii_alphabetic() {
+                run.push(c);
+                continue;
+            }
+            if !run.is_empty() {
+                terms.push(Term::Terminal(run.clone()));
+                run.clear();
+            }
+
+            let lower = c.to_ascii_lowercase();
+            if !self.letters.contains_key(&lower) {
+                let upper = c.to_ascii_uppercase();
+                let name = self.names.fresh(&format!("{}-or-{}", lower, upper));
+                let rhs = vec![
+                    Expression::from_parts(vec![Term::Terminal(lower.to_string())]),
+                    Expression::from_parts(vec![Term::Terminal(upper.to_string())]),
+                ];
+                let letter = self.define(name, rhs);
+                self.letters.insert(lower, letter);
+            }
+            terms.push(self.letters[&lower].clone());
+        }
+
+        if !run.is_empty() || terms.is_empty() {
+            terms.push(Term::Terminal(run));
+        }
+        terms
+    }
+
+    fn canonical(&mut self, name: &str) -> String {
+        self.canonical
+            .entry(name.to_ascii_lowercase())
+            .or_insert_with(|| name.to_string())
+            .clone()
+    }
+
+    fn define(&mut self, name: String, rhs: Vec<Expression>) -> Term {
+        let lhs = Term::Nonterminal(name);
+        self.productions
+            .push(Production::from_parts(lhs.clone(), rhs));
+        lhs
+    }
+}
+
+/// Parse rules, importing any core rules they reference but do not define
+fn lower(mut rules: Vec<Rule>) -> Grammar {
+    let defined: HashSet<String> = rules
+        .iter()
+        .map(|rule| rule.name.to_ascii_lowercase())
+        .collect();
+
+    let core = core_rules();
+    let mut imported: HashSet<String> = HashSet::new();
+    let mut pending = vec![];
+    for rule in &rules {
+        references(&rule.alternation, &mut pending);
+    }
+    while let Some(name) = pending.pop() {
+        let name = name.to_ascii_lowercase();
+        if defined.contains(&name) || imported.contains(&name) {
+            continue;
+        }
+        if let Some(rule) = core
+            .iter()
+            .find(|rule| rule.name.to_ascii_lowercase() == name)
+        {
+            imported.insert(name);
+            references(&rule.alternation, &mut pending);
+            rules.push(rule.clone());
+        }
+    }
+
+    // fresh names must not capture references to rules left undefined, or
+    // to prose
+    let mut used = vec![];
+    for rule in &rules {
+        used.push(rule.name.clone());
+        names(&rule.alternation, &mut used);
+    }
+    let mut desugar = Desugar {
+        names: FreshNames::new(used),
+        canonical: HashMap::new(),
+        letters: HashMap::new(),
+        productions: vec![],
+    };
+    for rule in &rules {
+        desugar.canonical(&rule.name);
+    }
+
+    let mut productions: Vec<Production> = vec![];
+    for rule in &rules {
+        let name = desugar.canonical(&rule.name);
+        let rhs = desugar.expressions(&name, &rule.alternation);
+        let lhs = Term::Nonterminal(name);
+
+        let existing = productions.iter_mut().find(|prod| prod.lhs == lhs);
+        match existing {
+            Some(prod) if rule.incremental => {
+                for expression in rhs {
+                    prod.add_to_rhs(expression);
+                }
+            }
+            _ => productions.push(Production::from_parts(lhs, rhs)),
+        }
+        productions.append(&mut desugar.productions);
+    }
+
+    Grammar::from_parts(productions)
+}
+
+/// Parse RFC 5234 ABNF into a `Grammar`
+pub(crate) fn parse(input: &str) -> Result<Grammar, Error> {
+    Ok(lower(parse_rules(input)?))
+}
+
+/// The RFC 5234 core rules as a `Grammar`
+pub(crate) fn core_grammar() -> Grammar {
+    lower(core_rules())
+}
+
+fn is_rulename(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => chars.all(|c| c.is_ascii_alphanumeric() || c == '-'),
+        _ => false,
+    }
+}
+
+/// Rule names for every nonterminal, unique ignoring case as ABNF requires
+fn rulenames(grammar: &Grammar) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    let mut used = HashSet::new();
+
+    let terms = grammar.productions_iter().flat_map(|prod| {
+        Some(&prod.lhs).into_iter().chain(
+            prod.rhs_iter()
+                .flat_map(|expr| expr.terms_iter())
+                .collect::<Vec<_>>(),
+        )
+    });
+
+    for term in terms {
+        let nt = match *term {
+            Term::Nonterminal(ref nt) => nt,
+            Term::Terminal(_) => continue,
+        };
+        if names.contains_key(nt) {
+            continue;
+        }
+
+        let mut base: String = nt
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        if !is_rulename(&base) {
+            base = format!("rule-{}", base);
+        }
+
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while used.contains(&name.to_ascii_lowercase()) {
+            suffix += 1;
+            name = format!("{}-{}", base, suffix);
+        }
+        used.insert(name.to_ascii_lowercase());
+        names.insert(nt.clone(), name);
+    }
+
+    names
+}
+
+fn write_terminal(out: &mut String, t: &str) {
+    let printable = t.chars().all(|c| (' '..='~').contains(&c) && c != '"');
+
+    if !printable {
+        let values: Vec<String> = t.chars().map(|c| format!("{:02X}", c as u32)).collect();
+        write!(out, "%x{}", values.join(".")).unwrap();
+    } else if t.chars().any(|c| c.is_ascii_alphabetic()) {
+        write!(out, "%s\"{}\"", t).unwrap();
+    } else {
+        write!(out, "\"{}\"", t).unwrap();
+    }
+}
+
+/// Print a `Grammar` as RFC 5234 ABNF, one rule per line.
+///
+/// Nonterminal names which are not valid ABNF rule names, or which differ
+/// from another only by case, are renamed.
+pub(crate) fn print(grammar: &Grammar) -> String {
+    let names = rulenames(grammar);
+    let mut out = String::new();
+    let mut defined = HashSet::new();
+
+    for production in grammar.productions_iter() {
+        let name = match production.lhs {
+            Term::Nonterminal(ref nt) => &names[nt],
+            Term::Terminal(_) => continue,
+        };
+        let incremental = !defined.insert(name.clone());
+        out.push_str(name);
+        out.push_str(if incremental { " =/ " } else { " = " });
+
+        for (index, expression) in production.rhs_iter().enumerate() {
+            if index > 0 {
+                out.push_str(" / ");
+            }
+            for (index, term) in expression.terms_iter().enumerate() {
+                if index > 0 {
+                    out.push(' ');
+                }
+                match *term {
+                    Term::Nonterminal(ref nt) => out.push_str(&names[nt]),
+                    Term::Terminal(ref t) => write_terminal(&mut out, t),
+                }
+            }
+        }
+        out.push_str("\r\n");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bnf(input: &str) -> Grammar {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn alternatives_and_concatenation() {
+        let grammar = parse("pair = bit bit\r\nbit = %s\"0\" / \"1\"\r\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<pair> ::= <bit> <bit>
+                <bit> ::= \"0\" | \"1\"")
+        );
+    }
+
+    #[test]
+    fn comments_and_continuation_lines() {
+        let grammar = parse(
+            "; leading comment\n\
+             pair = bit ; trailing comment\n\
+             \x20      bit\n\
+             \n\
+             bit = \"0\"\n\
+             \x20   / \"1\"",
+        )
+        .unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<pair> ::= <bit> <bit>
+                <bit> ::= \"0\" | \"1\"")
+        );
+    }
+
+    #[test]
+    fn case_insensitive_strings() {
+        let grammar = parse("method = \"Get!\" / %i\"go\"\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf(
+                "<method> ::= <g-or-G> <e-or-E> <t-or-T> \"!\" | <g-or-G> <o-or-O>
+                <g-or-G> ::= \"g\" | \"G\"
+                <e-or-E> ::= \"e\" | \"E\"
+                <t-or-T> ::= \"t\" | \"T\"
+                <o-or-O> ::= \"o\" | \"O\""
+            )
+        );
+    }
+
+    #[test]
+    fn numeric_values() {
+        let grammar = parse("crlf = %x0D.0A\nnine = %d57\nbits = %b1000001\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<crlf> ::= '\r\n'
+                <nine> ::= \"9\"
+                <bits> ::= \"A\"")
+        );
+    }
+
+    #[test]
+    fn value_ranges() {
+        let grammar = parse("abc = %x61-63\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<abc> ::= <abc-range>
+                <abc-range> ::= \"a\" | \"b\" | \"c\"")
+        );
+    }
+
+    #[test]
+    fn repetition() {
+        let grammar = parse("r = 2*3%s\"a\"\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<r> ::= \"a\" \"a\" <r-opt>
+                <r-opt> ::= \"\" | \"a\"")
+        );
+
+        let grammar = parse("r = *2%s\"a\" 3%s\"b\" 1*%s\"c\"\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<r> ::= <r-opt-2> \"b\" \"b\" \"b\" \"c\" <r-rep>
+                <r-opt> ::= \"\" | \"a\"
+                <r-opt-2> ::= \"\" | \"a\" <r-opt>
+                <r-rep> ::= \"\" | \"c\" <r-rep>")
+        );
+    }
+
+    #[test]
+    fn options_and_groups() {
+        let grammar = parse("r = [\"1\"] (\"2\" \"3\") (\"4\" / \"5\")\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<r> ::= <r-opt> \"2\" \"3\" <r-group>
+                <r-opt> ::= \"1\" | \"\"
+                <r-group> ::= \"4\" | \"5\"")
+        );
+    }
+
+    #[test]
+    fn fresh_names_avoid_references() {
+        let grammar = parse("r = [\"1\"] r-opt\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<r> ::= <r-opt-2> <r-opt>
+                <r-opt-2> ::= \"1\" | \"\"")
+        );
+    }
+
+    #[test]
+    fn fresh_names_avoid_prose() {
+        let grammar = parse("r = [\"1\"] <r-opt>\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<r> ::= <r-opt-2> <r-opt>
+                <r-opt-2> ::= \"1\" | \"\"")
+        );
+    }
+
+    #[test]
+    fn incremental_alternatives() {
+        let grammar = parse("r = \"1\"\nr =/ \"2\"\n").unwrap();
+        assert_eq!(grammar, bnf("<r> ::= \"1\" | \"2\""));
+    }
+
+    #[test]
+    fn rule_names_ignore_case() {
+        let grammar = parse("Rule = other\nOTHER = \"1\"\n").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<Rule> ::= <OTHER>
+                <OTHER> ::= \"1\"")
+        );
+    }
+
+    #[test]
+    fn prose() {
+        let grammar = parse("r = <any text>\n").unwrap();
+        assert_eq!(grammar, bnf("<r> ::= <any text>"));
+    }
+
+    #[test]
+    fn imports_core_rules() {
+        let grammar = parse("line = 1*digit CRLF\n").unwrap();
+        let defined: Vec<String> = grammar
+            .productions_iter()
+            .map(|prod| prod.lhs.to_string())
+            .collect();
+
+        assert!(defined.contains(&String::from("<DIGIT>")), "{:?}", defined);
+        assert!(defined.contains(&String::from("<CRLF>")), "{:?}", defined);
+        assert!(defined.contains(&String::from("<CR>")), "{:?}", defined);
+        assert!(defined.contains(&String::from("<LF>")), "{:?}", defined);
+        assert!(!defined.contains(&String::from("<ALPHA>")), "{:?}", defined);
+        assert!(grammar.parse_input("42\r\n").is_ok());
+    }
+
+    #[test]
+    fn core_rules_grammar() {
+        let grammar = core_grammar();
+        assert!(grammar.parse_input_from("HEXDIG", "f").is_ok());
+        assert!(grammar.parse_input_from("LWSP", " \r\n\t").is_ok());
+        assert!(grammar.parse_input_from("ALPHA", "1").is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        match parse("r = \"a\"\ns = %x110000\n") {
+            Err(Error::ParseError(ref e)) => {
+                assert_eq!((e.position.line, e.position.column), (2, 5));
+                assert_eq!(e.expected, "expected values which are valid characters");
+            }
+            e => panic!("should be Error::ParseError: {:?}", e),
+        }
+        assert!(parse("r = %x00-10FFFF\n").is_err());
+        assert!(parse("r = \n").is_err());
+        assert!(parse("= \"a\"\n").is_err());
+    }
+
+    #[test]
+    fn print_and_back() {
+        let grammar = bnf("<request> ::= <method> \" \" <path> '\r\n'
+            <method> ::= \"GET\" | \"PUT\"
+            <path> ::= \"/\" | \"/\" <segment> <path>
+            <segment> ::= \"a\" | '\"' | \"\"
+            <request> ::= <method>");
+
+        let abnf = print(&grammar);
+        assert_eq!(
+            abnf,
+            "request = method \" \" path %x0D.0A\r\n\
+             method = %s\"GET\" / %s\"PUT\"\r\n\
+             path = \"/\" / \"/\" segment path\r\n\
+             segment = %s\"a\" / %x22 / \"\"\r\n\
+             request =/ method\r\n"
+        );
+
+        let expected = bnf("<request> ::= <method> \" \" <path> '\r\n' | <method>
+            <method> ::= \"GET\" | \"PUT\"
+            <path> ::= \"/\" | \"/\" <segment> <path>
+            <segment> ::= \"a\" | '\"' | \"\"");
+        assert_eq!(parse(&abnf).unwrap(), expected);
+    }
+
+    #[test]
+    fn print_renames_invalid_rule_names() {
+        let grammar = bnf("<a b> ::= <A_B> | <a-b> | <1st>
+            <A_B> ::= \"x\"");
+
+        assert_eq!(
+            print(&grammar),
+            "a-b = A-B-2 / a-b-3 / rule-1st\r\n\
+             A-B-2 = %s\"x\"\r\n"
+        );
+    }
+}
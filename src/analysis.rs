@@ -0,0 +1,452 @@
+use compiled::{CompiledGrammar, Symbol};
+use expression::Expression;
+use grammar::Grammar;
+use std::collections::{BTreeMap, BTreeSet};
+use term::Term;
+
+/// A GrammarAnalysis holds properties of a `Grammar`'s nonterminals,
+/// computed once up front
+///
+/// For productivity and depth, nonterminals without any production are
+/// treated like terminals, as `Grammar::generate` emits them as literal text,
+/// so they are counted neither productive nor unproductive. For nullable,
+/// FIRST and FOLLOW sets they derive nothing at all.
+///
+/// Terminal sets hold whole terminals, and use the empty terminal `""` for
+/// the empty string in FIRST sets and for the end of input in FOLLOW sets.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GrammarAnalysis {
+    min_depths: BTreeMap<String, usize>,
+    unproductive: BTreeSet<String>,
+    nullable: BTreeSet<String>,
+    first: BTreeMap<String, BTreeSet<String>>,
+    follow: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl GrammarAnalysis {
+    /// Analyse `grammar`
+    pub fn new(grammar: &Grammar) -> GrammarAnalysis {
+        let compiled = grammar.compiled();
+        let symbols = compiled.symbols();
+        let name = |symbol: Symbol| match *symbols.term(symbol) {
+            Term::Nonterminal(ref s) | Term::Terminal(ref s) => s.clone(),
+        };
+        let defined: Vec<Symbol> = symbols
+            .iter()
+            .map(|(symbol, _)| symbol)
+            .filter(|&symbol| compiled.is_defined(symbol))
+            .collect();
+
+        let depths = min_depths(compiled);
+        let sets = Sets::new(compiled);
+        let follow = sets.follow(compiled);
+        let terminals = |set: &BTreeSet<Option<Symbol>>| -> BTreeSet<String> {
+            set.iter()
+                .map(|terminal| terminal.map_or_else(String::new, name))
+                .collect()
+        };
+
+        GrammarAnalysis {
+            min_depths: defined
+                .iter()
+                .filter_map(|&nt| depths[nt.index()].map(|depth| (name(nt), depth)))
+                .collect(),
+            unproductive: defined
+                .iter()
+                .filter(|nt| depths[nt.index()].is_none())
+                .map(|&nt| name(nt))
+                .collect(),
+            nullable: defined
+                .iter()
+                .filter(|nt| sets.nullable[nt.index()])
+                .map(|&nt| name(nt))
+                .collect(),
+            first: defined
+                .iter()
+                .map(|&nt| {
+                    (
+                        name(nt),
+                        sets.first[nt.index()].iter().map(|&t| name(t)).collect(),
+                    )
+                })
+                .collect(),
+            follow: defined
+                .iter()
+                .map(|&nt| (name(nt), terminals(&follow[nt.index()])))
+                .collect(),
+        }
+    }
+
+    /// Whether `nonterminal` has a production and can derive a string of terminals
+    pub fn is_productive(&self, nonterminal: &str) -> bool {
+        self.min_depths.contains_key(nonterminal)
+    }
+
+    /// Nonterminals which can derive a string of terminals, in sorted order
+    pub fn productive(&self) -> Vec<&str> {
+        self.min_depths.keys().map(String::as_str).collect()
+    }
+
+    /// Nonterminals which have a production but can never derive a string of
+    /// terminals, in sorted order
+    pub fn unproductive(&self) -> Vec<&str> {
+        self.unproductive.iter().map(String::as_str).collect()
+    }
+
+    /// The fewest levels of productions needed to derive a string of
+    /// terminals from `nonterminal`, or `None` when it is not productive.
+    /// A production whose expression holds only terminals has depth 1.
+    pub fn min_depth(&self, nonterminal: &str) -> Option<usize> {
+        self.min_depths.get(nonterminal).cloned()
+    }
+
+    /// Whether `nonterminal` can derive the empty string
+    pub fn is_nullable(&self, nonterminal: &str) -> bool {
+        self.nullable.contains(nonterminal)
+    }
+
+    /// Nonterminals which can derive the empty string, in sorted order
+    pub fn nullable(&self) -> Vec<&str> {
+        self.nullable.iter().map(String::as_str).collect()
+    }
+
+    /// The terminals that strings derived from `nonterminal` can begin with,
+    /// including `""` when it is nullable, or `None` when it has no production
+    pub fn first(&self, nonterminal: &str) -> Option<BTreeSet<String>> {
+        self.first.get(nonterminal).map(|first| {
+            let mut first = first.clone();
+            if self.is_nullable(nonterminal) {
+                first.insert(String::new());
+            }
+            first
+        })
+    }
+
+    /// The terminals that strings derived from a sequence of `terms` can begin
+    /// with, including `""` when they can all derive the empty string
+    ///
+    /// Pass `expression.terms_iter().skip(n)` for the suffix of an expression.
+    pub fn first_of<'a, I>(&self, terms: I) -> BTreeSet<String>
+    where
+        I: IntoIterator<Item = &'a Term>,
+    {
+        let mut first = BTreeSet::new();
+        for term in terms {
+            match *term {
+                Term::Terminal(ref t) if t.is_empty() => continue,
+                Term::Terminal(ref t) => {
+                    first.insert(t.clone());
+                    return first;
+                }
+                Term::Nonterminal(ref nt) => {
+                    if let Some(nt_first) = self.first.get(nt) {
+                        first.extend(nt_first.iter().cloned());
+                    }
+                    if !self.is_nullable(nt) {
+                        return first;
+                    }
+                }
+            }
+        }
+        first.insert(String::new());
+        first
+    }
+
+    /// The terminals which can come right after `nonterminal` in a sentence
+    /// derived from the start symbol, with `""` standing for the end of
+    /// input, or `None` when it has no production
+    pub fn follow(&self, nonterminal: &str) -> Option<&BTreeSet<String>> {
+        self.follow.get(nonterminal)
+    }
+
+    /// The fewest levels of productions needed to derive a string of
+    /// terminals from `expression` when it is used as an alternative, or
+    /// `None` when one of its nonterminals is not productive
+    pub fn expression_min_depth(&self, expression: &Expression) -> Option<usize> {
+        expression
+            .terms_iter()
+            .try_fold(1, |depth, term| match *term {
+                Term::Nonterminal(ref nt) if self.unproductive.contains(nt) => None,
+                Term::Nonterminal(ref nt) => Some(
+                    self.min_depth(nt)
+                        .map_or(depth, |min_depth| depth.max(min_depth + 1)),
+                ),
+                Term::Terminal(_) => Some(depth),
+            })
+    }
+}
+
+/// The fewest levels of productions needed to derive a string of terminals
+/// from each symbol, by symbol index
+///
+/// Terminals and nonterminals without a production are taken as depth 0, and
+/// unproductive nonterminals as `None`.
+pub(crate) fn min_depths(compiled: &CompiledGrammar) -> Vec<Option<usize>> {
+    let mut min_depths: Vec<Option<usize>> = compiled
+        .symbols()
+        .iter()
+        .map(|(symbol, _)| {
+            if compiled.is_defined(symbol) {
+                None
+            } else {
+                Some(0)
+            }
+        })
+        .collect();
+
+    // the depth of an expression is one more than its deepest nonterminal,
+    // so iterate until no nonterminal finds a shallower expression
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (lhs, expression) in compiled.rules() {
+            let depth = match expression_min_depth(&min_depths, expression) {
+                Some(depth) => depth,
+                None => continue,
+            };
+            if min_depths[lhs.index()].map_or(true, |known| depth < known) {
+                min_depths[lhs.index()] = Some(depth);
+                changed = true;
+            }
+        }
+    }
+    min_depths
+}
+
+/// The depth of `expression` used as an alternative, given `min_depths`
+pub(crate) fn expression_min_depth(
+    min_depths: &[Option<usize>],
+    expression: &[Symbol],
+) -> Option<usize> {
+    expression.iter().try_fold(1, |depth, symbol| {
+        min_depths[symbol.index()].map(|min_depth| depth.max(min_depth + 1))
+    })
+}
+
+/// Nullable and FIRST sets by symbol index, the FIRST sets without the empty
+/// terminal
+struct Sets<'a> {
+    compiled: &'a CompiledGrammar,
+    nullable: Vec<bool>,
+    first: Vec<BTreeSet<Symbol>>,
+}
+
+impl<'a> Sets<'a> {
+    /// Grow nullable and FIRST sets until they no longer change
+    fn new(compiled: &'a CompiledGrammar) -> Sets<'a> {
+        let count = compiled.symbols().len();
+        let mut sets = Sets {
+            compiled,
+            nullable: vec![false; count],
+            first: vec![BTreeSet::new(); count],
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (lhs, expression) in compiled.rules() {
+                let (first, nullable) = sets.first_of(expression);
+                if nullable && !sets.nullable[lhs.index()] {
+                    sets.nullable[lhs.index()] = true;
+                    changed = true;
+                }
+                for terminal in first {
+                    changed |= sets.first[lhs.index()].insert(terminal);
+                }
+            }
+        }
+        sets
+    }
+
+    /// The FIRST set of a sequence of symbols, and whether they can all
+    /// derive the empty string
+    fn first_of(&self, terms: &[Symbol]) -> (BTreeSet<Symbol>, bool) {
+        let symbols = self.compiled.symbols();
+        let mut first = BTreeSet::new();
+        for &symbol in terms {
+            match *symbols.term(symbol) {
+                Term::Terminal(ref t) if t.is_empty() => continue,
+                Term::Terminal(_) => {
+                    first.insert(symbol);
+                    return (first, false);
+                }
+                Term::Nonterminal(_) => {
+                    first.extend(self.first[symbol.index()].iter().cloned());
+                    if !self.nullable[symbol.index()] {
+                        return (first, false);
+                    }
+                }
+            }
+        }
+        (first, true)
+    }
+
+    /// FOLLOW sets by symbol index, grown until they no longer change,
+    /// starting from the end of input, `None`, following the start symbol:
+    /// the declared start, or else the first production's lhs
+    fn follow(&self, compiled: &CompiledGrammar) -> Vec<BTreeSet<Option<Symbol>>> {
+        let mut follow = vec![BTreeSet::new(); compiled.symbols().len()];
+        if let Some(start) = compiled.start() {
+            if compiled.is_defined(start) {
+                follow[start.index()].insert(None);
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (lhs, expression) in compiled.rules() {
+                for (index, &nt) in expression.iter().enumerate() {
+                    if !compiled.is_defined(nt) {
+                        continue;
+                    }
+                    let (first, nullable) = self.first_of(&expression[index + 1..]);
+                    let mut found: BTreeSet<Option<Symbol>> = first.into_iter().map(Some).collect();
+                    if nullable {
+                        found.extend(follow[lhs.index()].iter().cloned());
+                    }
+                    for terminal in found {
+                        changed |= follow[nt.index()].insert(terminal);
+                    }
+                }
+            }
+        }
+        follow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn productive_grammar() {
+        let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let analysis = GrammarAnalysis::new(&grammar);
+
+        assert_eq!(analysis.productive(), vec!["base", "dna"]);
+        assert!(analysis.unproductive().is_empty());
+        assert_eq!(analysis.min_depth("base"), Some(1));
+        assert_eq!(analysis.min_depth("dna"), Some(2));
+    }
+
+    #[test]
+    fn unproductive_nonterminals() {
+        let grammar: Grammar = "<start> ::= <a> | <loop> <a>
+            <a> ::= \"a\" | <b> <c>
+            <b> ::= <b> \"b\" | <c>
+            <c> ::= <loop> \"c\"
+            <loop> ::= <loop>
+            <empty> ::= \"\""
+            .parse()
+            .unwrap();
+        let analysis = GrammarAnalysis::new(&grammar);
+
+        assert_eq!(analysis.productive(), vec!["a", "empty", "start"]);
+        assert_eq!(analysis.unproductive(), vec!["b", "c", "loop"]);
+        assert_eq!(analysis.min_depth("start"), Some(2));
+        assert_eq!(analysis.min_depth("loop"), None);
+        assert!(!analysis.is_productive("b"));
+    }
+
+    #[test]
+    fn min_depth_takes_shallowest_alternative() {
+        let grammar: Grammar = "<s> ::= <a> <a> | <b>
+            <a> ::= <b> | \"a\"
+            <b> ::= <c>
+            <c> ::= <d>
+            <d> ::= \"d\""
+            .parse()
+            .unwrap();
+        let analysis = GrammarAnalysis::new(&grammar);
+
+        assert_eq!(analysis.min_depth("a"), Some(1));
+        assert_eq!(analysis.min_depth("s"), Some(2));
+        assert_eq!(analysis.min_depth("b"), Some(3));
+
+        let expression = Expression::from_parts(vec![
+            Term::Terminal(String::from("x")),
+            Term::Nonterminal(String::from("b")),
+            Term::Nonterminal(String::from("a")),
+            Term::Nonterminal(String::from("not-defined")),
+        ]);
+        assert_eq!(analysis.expression_min_depth(&expression), Some(4));
+        assert_eq!(analysis.expression_min_depth(&Expression::new()), Some(1));
+    }
+
+    fn set(terminals: &[&str]) -> BTreeSet<String> {
+        terminals.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn first_and_follow() {
+        let grammar: Grammar = "<expr> ::= <term> <expr-rest>
+            <expr-rest> ::= \"+\" <term> <expr-rest> | \"\"
+            <term> ::= <factor> <term-rest>
+            <term-rest> ::= \"*\" <factor> <term-rest> | \"\"
+            <factor> ::= \"(\" <expr> \")\" | \"id\""
+            .parse()
+            .unwrap();
+        let analysis = GrammarAnalysis::new(&grammar);
+
+        assert_eq!(analysis.nullable(), vec!["expr-rest", "term-rest"]);
+        assert_eq!(analysis.first("expr"), Some(set(&["(", "id"])));
+        assert_eq!(analysis.first("expr-rest"), Some(set(&["", "+"])));
+        assert_eq!(analysis.first("term-rest"), Some(set(&["", "*"])));
+        assert_eq!(analysis.follow("expr"), Some(&set(&["", ")"])));
+        assert_eq!(analysis.follow("expr-rest"), Some(&set(&["", ")"])));
+        assert_eq!(analysis.follow("term"), Some(&set(&["", ")", "+"])));
+        assert_eq!(analysis.follow("term-rest"), Some(&set(&["", ")", "+"])));
+        assert_eq!(analysis.follow("factor"), Some(&set(&["", ")", "*", "+"])));
+        assert_eq!(analysis.first("undefined"), None);
+        assert_eq!(analysis.follow("undefined"), None);
+    }
+
+    #[test]
+    fn first_of_suffixes() {
+        let grammar: Grammar = "<s> ::= <a> <b> \"c\" | <undefined> \"d\"
+            <a> ::= \"a\" | \"\"
+            <b> ::= <a> <a>"
+            .parse()
+            .unwrap();
+        let analysis = GrammarAnalysis::new(&grammar);
+        let expression = grammar
+            .productions_iter()
+            .next()
+            .unwrap()
+            .rhs_iter()
+            .next()
+            .unwrap();
+
+        assert_eq!(analysis.nullable(), vec!["a", "b"]);
+        assert_eq!(analysis.first_of(expression.terms_iter()), set(&["a", "c"]));
+        assert_eq!(
+            analysis.first_of(expression.terms_iter().skip(1)),
+            set(&["a", "c"])
+        );
+        assert_eq!(
+            analysis.first_of(expression.terms_iter().skip(2)),
+            set(&["c"])
+        );
+        assert_eq!(
+            analysis.first_of(expression.terms_iter().skip(3)),
+            set(&[""])
+        );
+        assert_eq!(analysis.first("s"), Some(set(&["a", "c"])));
+        assert_eq!(analysis.follow("a"), Some(&set(&["a", "c"])));
+        assert_eq!(analysis.follow("s"), Some(&set(&[""])));
+    }
+
+    #[test]
+    fn undefined_nonterminals_are_literal() {
+        let grammar: Grammar = "<start> ::= <not-defined>".parse().unwrap();
+        let analysis = GrammarAnalysis::new(&grammar);
+
+        assert_eq!(analysis.min_depth("start"), Some(1));
+        assert!(!analysis.is_productive("not-defined"));
+        assert!(analysis.unproductive().is_empty());
+    }
+}
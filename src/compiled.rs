@@ -0,0 +1,388 @@
+use expression::Expression;
+use grammar::Grammar;
+use production::Production;
+use std::collections::HashMap;
+use std::sync::Arc;
+use term::Term;
+
+/// A Symbol is a `Term` interned in a `SymbolTable`, and only has meaning
+/// alongside that table
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The position of the `Symbol` in its table, counting from 0 in order
+    /// of first appearance
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A SymbolTable maps each distinct `Term` of a grammar to a `Symbol`
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    terms: Vec<Term>,
+    terminals: HashMap<String, Symbol>,
+    nonterminals: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    /// Get the `Symbol` for `term`, adding it if it is new
+    pub(crate) fn intern(&mut self, term: &Term) -> Symbol {
+        if let Some(symbol) = self.get(term) {
+            return symbol;
+        }
+
+        let symbol = Symbol(self.terms.len() as u32);
+        match *term {
+            Term::Terminal(ref t) => self.terminals.insert(t.clone(), symbol),
+            Term::Nonterminal(ref nt) => self.nonterminals.insert(nt.clone(), symbol),
+        };
+        self.terms.push(term.clone());
+        symbol
+    }
+
+    /// The `Symbol` for `term`, if it is in the table
+    pub fn get(&self, term: &Term) -> Option<Symbol> {
+        match *term {
+            Term::Terminal(ref t) => self.terminal(t),
+            Term::Nonterminal(ref nt) => self.nonterminal(nt),
+        }
+    }
+
+    /// The `Symbol` for the terminal `text`, if it is in the table
+    pub fn terminal(&self, text: &str) -> Option<Symbol> {
+        self.terminals.get(text).cloned()
+    }
+
+    /// The `Symbol` for the nonterminal `name`, if it is in the table
+    pub fn nonterminal(&self, name: &str) -> Option<Symbol> {
+        self.nonterminals.get(name).cloned()
+    }
+
+    /// The `Term` interned as `symbol`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` came from a larger table.
+    pub fn term(&self, symbol: Symbol) -> &Term {
+        &self.terms[symbol.index()]
+    }
+
+    /// Whether `symbol` is a terminal
+    pub fn is_terminal(&self, symbol: Symbol) -> bool {
+        match *self.term(symbol) {
+            Term::Terminal(_) => true,
+            Term::Nonterminal(_) => false,
+        }
+    }
+
+    /// Every `Symbol` with its `Term`, in order of index
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &Term)> {
+        self.terms
+            .iter()
+            .enumerate()
+            .map(|(index, term)| (Symbol(index as u32), term))
+    }
+
+    /// The number of symbols in the table
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Whether the table holds no symbols
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+/// A production with its terms replaced by symbols
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct CompiledProduction {
+    lhs: Symbol,
+    rhs: Vec<Vec<Symbol>>,
+}
+
+/// A CompiledGrammar is a `Grammar` with every `Term` interned as a `Symbol`
+///
+/// Its parts are shared between clones, so cloning is cheap, as is comparing
+/// clones. Comments and source spans are not kept.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate bnf;
+/// use bnf::{Grammar, Term};
+///
+/// fn main() {
+///     let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+///         .parse()
+///         .unwrap();
+///     let compiled = grammar.compile();
+///     let symbols = compiled.symbols();
+///
+///     let dna = compiled.start().unwrap();
+///     assert_eq!(symbols.term(dna), &Term::Nonterminal(String::from("dna")));
+///     assert_eq!(compiled.expressions(dna).count(), 2);
+///     assert_eq!(symbols.len(), 6);
+///     assert_eq!(compiled.to_grammar(), grammar);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompiledGrammar {
+    symbols: Arc<SymbolTable>,
+    productions: Arc<Vec<CompiledProduction>>,
+    declared_start: Option<Symbol>,
+    /// Positions in `productions` defining each symbol, by symbol index
+    by_lhs: Arc<Vec<Vec<usize>>>,
+}
+
+impl CompiledGrammar {
+    /// Intern every term of `grammar`
+    pub(crate) fn new(grammar: &Grammar) -> CompiledGrammar {
+        let mut compiled = CompiledGrammar {
+            symbols: Arc::new(SymbolTable::default()),
+            productions: Arc::new(vec![]),
+            declared_start: None,
+            by_lhs: Arc::new(vec![]),
+        };
+        if let Some(start) = grammar.declared_start() {
+            let start = Term::Nonterminal(start.to_string());
+            compiled.declared_start = Some(Arc::make_mut(&mut compiled.symbols).intern(&start));
+        }
+        for production in grammar.productions_iter() {
+            compiled.push(production);
+        }
+        compiled
+    }
+
+    /// Add `production` after the others, in step with `Grammar::add_production`
+    pub(crate) fn push(&mut self, production: &Production) {
+        let symbols = Arc::make_mut(&mut self.symbols);
+        let lhs = symbols.intern(&production.lhs);
+        let rhs = production
+            .rhs_iter()
+            .map(|expression| {
+                expression
+                    .terms_iter()
+                    .map(|term| symbols.intern(term))
+                    .collect()
+            })
+            .collect();
+
+        let by_lhs = Arc::make_mut(&mut self.by_lhs);
+        by_lhs.resize(symbols.len(), vec![]);
+        by_lhs[lhs.index()].push(self.productions.len());
+        Arc::make_mut(&mut self.productions).push(CompiledProduction { lhs, rhs });
+    }
+
+    /// The table of every symbol in the grammar
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// The declared start symbol, or else the left hand side of the first
+    /// production
+    pub fn start(&self) -> Option<Symbol> {
+        self.declared_start
+            .or_else(|| self.productions.first().map(|production| production.lhs))
+    }
+
+    /// The expressions of every production defining `nonterminal`, in
+    /// grammar order
+    pub fn expressions(&self, nonterminal: Symbol) -> impl Iterator<Item = &[Symbol]> {
+        self.positions(nonterminal)
+            .iter()
+            .flat_map(move |&position| self.productions[position].rhs.iter())
+            .map(Vec::as_slice)
+    }
+
+    /// Positions of the productions defining `symbol`
+    pub(crate) fn positions(&self, symbol: Symbol) -> &[usize] {
+        self.by_lhs.get(symbol.index()).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether `symbol` is a nonterminal with a production
+    pub(crate) fn is_defined(&self, symbol: Symbol) -> bool {
+        !self.symbols.is_terminal(symbol) && !self.positions(symbol).is_empty()
+    }
+
+    /// The nonterminals reachable from `start`, including itself, in the
+    /// order they are found
+    pub(crate) fn reachable(&self, start: Symbol) -> Vec<Symbol> {
+        let mut seen = vec![false; self.symbols.len()];
+        seen[start.index()] = true;
+        let mut reached = vec![start];
+        let mut next = 0;
+        while let Some(&symbol) = reached.get(next) {
+            next += 1;
+            for &term in self.expressions(symbol).flatten() {
+                if !self.symbols.is_terminal(term) && !seen[term.index()] {
+                    seen[term.index()] = true;
+                    reached.push(term);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Every expression of a production with a nonterminal lhs, alongside
+    /// that lhs
+    pub(crate) fn rules(&self) -> impl Iterator<Item = (Symbol, &[Symbol])> {
+        self.productions
+            .iter()
+            .filter(move |production| !self.symbols.is_terminal(production.lhs))
+            .flat_map(|production| {
+                production
+                    .rhs
+                    .iter()
+                    .map(move |expression| (production.lhs, expression.as_slice()))
+            })
+    }
+
+    /// Convert back to a `Grammar`
+    pub fn to_grammar(&self) -> Grammar {
+        let term = |symbol: &Symbol| self.symbols.term(*symbol).clone();
+        let mut grammar = Grammar::from_parts(
+            self.productions
+                .iter()
+                .map(|production| {
+                    Production::from_parts(
+                        term(&production.lhs),
+                        production
+                            .rhs
+                            .iter()
+                            .map(|expression| {
+                                Expression::from_parts(expression.iter().map(term).collect())
+                            })
+                            .collect(),
+                    )
+                })
+                .collect(),
+        );
+        grammar.set_start_symbol(self.declared_start.map(|start| match term(&start) {
+            Term::Nonterminal(nt) | Term::Terminal(nt) => nt,
+        }));
+        grammar
+    }
+}
+
+impl PartialEq for CompiledGrammar {
+    fn eq(&self, other: &CompiledGrammar) -> bool {
+        if Arc::ptr_eq(&self.symbols, &other.symbols) {
+            return self.declared_start == other.declared_start
+                && (Arc::ptr_eq(&self.productions, &other.productions)
+                    || self.productions == other.productions);
+        }
+
+        // symbols from different tables are compared by their terms
+        let same = |a: &Symbol, b: &Symbol| self.symbols.term(*a) == other.symbols.term(*b);
+        let same_start = match (self.declared_start, other.declared_start) {
+            (Some(a), Some(b)) => same(&a, &b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        same_start
+            && self.productions.len() == other.productions.len()
+            && self
+                .productions
+                .iter()
+                .zip(other.productions.iter())
+                .all(|(a, b)| {
+                    same(&a.lhs, &b.lhs)
+                        && a.rhs.len() == b.rhs.len()
+                        && a.rhs.iter().zip(&b.rhs).all(|(a, b)| {
+                            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+                        })
+                })
+    }
+}
+
+impl Eq for CompiledGrammar {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dna() -> Grammar {
+        "<dna> ::= <base> | <base> <dna>
+        <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn interns_each_term_once() {
+        let mut grammar: Grammar = "<a> ::= \"x\" <a> | \"x\" <b>".parse().unwrap();
+        grammar.add_production(Production::from_parts(
+            Term::Terminal(String::from("t")),
+            vec![Expression::from_parts(vec![Term::Terminal(String::from(
+                "x",
+            ))])],
+        ));
+        let compiled = grammar.compile();
+        let symbols = compiled.symbols();
+
+        assert_eq!(symbols.len(), 4);
+        let a = symbols.nonterminal("a").unwrap();
+        let x = symbols.terminal("x").unwrap();
+        let b = symbols.get(&Term::Nonterminal(String::from("b"))).unwrap();
+        assert_eq!((a.index(), x.index(), b.index()), (0, 1, 2));
+        assert!(symbols.is_terminal(x) && !symbols.is_terminal(b));
+        assert_eq!(symbols.terminal("a"), None);
+        assert_eq!(
+            symbols.iter().nth(1),
+            Some((x, &Term::Terminal(String::from("x"))))
+        );
+
+        assert_eq!(
+            compiled.expressions(a).collect::<Vec<_>>(),
+            vec![&[x, a][..], &[x, b][..]]
+        );
+        assert_eq!(compiled.expressions(b).count(), 0);
+        assert!(compiled.is_defined(a) && !compiled.is_defined(b));
+        assert_eq!(compiled.rules().count(), 2);
+        assert_eq!(compiled.to_grammar(), grammar);
+    }
+
+    #[test]
+    fn compares_across_tables() {
+        let grammar = dna();
+        let compiled = grammar.compile();
+        assert_eq!(compiled, compiled.clone());
+        assert_eq!(compiled, CompiledGrammar::new(&grammar));
+
+        let mut other = CompiledGrammar::new(&grammar);
+        other.push(&"<rna> ::= \"U\"".parse().unwrap());
+        assert_ne!(compiled, other);
+
+        let reordered: Grammar = "<base> ::= \"A\" | \"C\" | \"G\" | \"T\"
+            <dna> ::= <base> | <base> <dna>"
+            .parse()
+            .unwrap();
+        assert_ne!(compiled, reordered.compile());
+    }
+
+    #[test]
+    fn compares_start_of_shared_tables() {
+        let compiled = dna().compile();
+        let mut started = compiled.clone();
+        started.declared_start = compiled.symbols().nonterminal("base");
+
+        assert!(Arc::ptr_eq(&compiled.productions, &started.productions));
+        assert_ne!(compiled, started);
+    }
+
+    #[test]
+    fn grammar_keeps_compiled_in_step() {
+        let mut grammar = dna();
+        let before = grammar.compile();
+        grammar.add_production("<rna> ::= \"U\"".parse().unwrap());
+        let after = grammar.compile();
+
+        assert_ne!(before, after);
+        assert_eq!(before.symbols().nonterminal("rna"), None);
+        assert_eq!(after.to_grammar(), grammar);
+        assert_eq!(after, CompiledGrammar::new(&grammar));
+    }
+}
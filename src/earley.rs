@@ -0,0 +1,623 @@
+use error::Error;
+use expression::Expression;
+use grammar::Grammar;
+use parse_forest::{ForestChild, PackedNode, ParseForest, SymbolNode};
+use parse_tree::{ParseTree, ParseTreeNode};
+use production::Production;
+use stacker;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use term::Term;
+
+/// Stack left before a recursive walk over a derivation, which nests as
+/// deeply as the input is long, continues on a freshly allocated segment
+pub(crate) const STACK_RED_ZONE: usize = 64 * 1024;
+/// Size of each stack segment allocated once in the red zone
+pub(crate) const STACK_SEGMENT: usize = 1024 * 1024;
+
+/// A single alternative of a `Production`, flattened for indexed access
+struct Rule<'gram> {
+    production: &'gram Production,
+    expression: &'gram Expression,
+    lhs: &'gram str,
+    terms: Vec<&'gram Term>,
+}
+
+/// An Earley item: a rule, how many of its terms have been matched (`dot`),
+/// and the input offset where matching began (`origin`)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Item {
+    fn advance(self) -> Item {
+        Item {
+            dot: self.dot + 1,
+            ..self
+        }
+    }
+}
+
+/// The result of running the Earley recognizer over some input.
+///
+/// Every set is indexed by byte offset into the input, so terminals of any
+/// length (including the empty terminal `""`) can be scanned directly.
+pub(crate) struct Chart<'gram, 'input> {
+    rules: Vec<Rule<'gram>>,
+    input: &'input str,
+    items: Vec<HashSet<Item>>,
+    /// completed rules keyed by (end offset, lhs), holding (origin, rule)
+    completed: HashMap<(usize, &'gram str), Vec<(usize, usize)>>,
+}
+
+impl<'gram, 'input> Chart<'gram, 'input> {
+    /// Flatten the `Grammar`'s alternatives, ready to recognize `input`
+    pub(crate) fn new(grammar: &'gram Grammar, input: &'input str) -> Chart<'gram, 'input> {
+        let mut rules = vec![];
+        for production in grammar.productions_iter() {
+            let lhs = match production.lhs {
+                Term::Nonterminal(ref nt) => nt.as_str(),
+                Term::Terminal(_) => continue,
+            };
+            for expression in production.rhs_iter() {
+                rules.push(Rule {
+                    production,
+                    expression,
+                    lhs,
+                    terms: expression.terms_iter().collect(),
+                });
+            }
+        }
+
+        Chart {
+            rules,
+            input,
+            items: vec![],
+            completed: HashMap::new(),
+        }
+    }
+
+    /// Run the recognizer for `start` over all of `input`
+    pub(crate) fn recognize(&mut self, start: &str) {
+        let mut by_lhs: HashMap<&'gram str, Vec<usize>> = HashMap::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            by_lhs.entry(rule.lhs).or_default().push(index);
+        }
+
+        let len = self.input.len();
+        let mut sets: Vec<Vec<Item>> = vec![vec![]; len + 1];
+        self.items = vec![HashSet::new(); len + 1];
+        self.completed.clear();
+
+        if let Some(starts) = by_lhs.get(start) {
+            for &rule in starts {
+                let item = Item {
+                    rule,
+                    dot: 0,
+                    origin: 0,
+                };
+                self.items[0].insert(item);
+                sets[0].push(item);
+            }
+        }
+
+        for offset in 0..=len {
+            if sets[offset].is_empty() {
+                continue;
+            }
+
+            // nonterminals which derived the empty string at this offset, so
+            // items predicting them after their completion still advance
+            let mut nullable: HashSet<&'gram str> = HashSet::new();
+            let mut next = 0;
+
+            while next < sets[offset].len() {
+                let item = sets[offset][next];
+                next += 1;
+
+                let rule = &self.rules[item.rule];
+                let mut advanced = vec![];
+
+                match rule.terms.get(item.dot) {
+                    None => {
+                        if item.origin == offset {
+                            nullable.insert(rule.lhs);
+                        }
+                        self.completed
+                            .entry((offset, rule.lhs))
+                            .or_default()
+                            .push((item.origin, item.rule));
+                        for parent in &sets[item.origin] {
+                            let waiting = match self.rules[parent.rule].terms.get(parent.dot) {
+                                Some(Term::Nonterminal(nt)) => nt == rule.lhs,
+                                _ => false,
+                            };
+                            if waiting {
+                                advanced.push((offset, parent.advance()));
+                            }
+                        }
+                    }
+                    Some(Term::Nonterminal(nt)) => {
+                        if let Some(predicted) = by_lhs.get(nt.as_str()) {
+                            for &predicted in predicted {
+                                let prediction = Item {
+                                    rule: predicted,
+                                    dot: 0,
+                                    origin: offset,
+                                };
+                                advanced.push((offset, prediction));
+                            }
+                        }
+                        if nullable.contains(nt.as_str()) {
+                            advanced.push((offset, item.advance()));
+                        }
+                    }
+                    Some(Term::Terminal(t)) => {
+                        if self.input[offset..].starts_with(t.as_str()) {
+                            advanced.push((offset + t.len(), item.advance()));
+                        }
+                    }
+                }
+
+                for (at, new_item) in advanced {
+                    if self.items[at].insert(new_item) {
+                        sets[at].push(new_item);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether `start` derived the entire input
+    pub(crate) fn accepts(&self, start: &'gram str) -> bool {
+        self.origins(start, self.input.len())
+            .any(|(origin, _)| origin == 0)
+    }
+
+    /// Furthest input offset any item reached, for error reporting
+    pub(crate) fn furthest_offset(&self) -> usize {
+        self.items
+            .iter()
+            .rposition(|set| !set.is_empty())
+            .unwrap_or(0)
+    }
+
+    /// Completed (origin, rule) pairs for `lhs` ending at `end`
+    fn origins<'a>(
+        &'a self,
+        lhs: &'gram str,
+        end: usize,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.completed
+            .get(&(end, lhs))
+            .into_iter()
+            .flat_map(|completions| completions.iter().cloned())
+    }
+
+    /// Build the `ParseForest` of every derivation of the input from `start`
+    fn forest(&self, start: &'gram str) -> ParseForest<'gram> {
+        let root = (start, 0, self.input.len());
+        let mut index: HashMap<(&'gram str, usize, usize), usize> = HashMap::new();
+        let mut nodes = vec![];
+        let mut pending = vec![root];
+        index.insert(root, 0);
+        nodes.push(SymbolNode {
+            span: 0..self.input.len(),
+            packed: vec![],
+        });
+
+        while let Some((lhs, start, end)) = pending.pop() {
+            let node = index[&(lhs, start, end)];
+            let mut packed = vec![];
+
+            let rules: Vec<usize> = self
+                .origins(lhs, end)
+                .filter(|&(origin, _)| origin == start)
+                .map(|(_, rule)| rule)
+                .collect();
+
+            for rule_index in rules {
+                let rule = &self.rules[rule_index];
+                let mut splits = vec![];
+                self.splits(
+                    rule_index,
+                    rule.terms.len(),
+                    start,
+                    end,
+                    &mut vec![],
+                    &mut splits,
+                );
+
+                for split in splits {
+                    let children = split
+                        .into_iter()
+                        .rev()
+                        .map(|child| match child {
+                            Split::Terminal(t, span) => ForestChild::Terminal(t, span),
+                            Split::Nonterminal(key) => {
+                                let next = nodes.len();
+                                let child = *index.entry(key).or_insert(next);
+                                if child == next {
+                                    nodes.push(SymbolNode {
+                                        span: key.1..key.2,
+                                        packed: vec![],
+                                    });
+                                    pending.push(key);
+                                }
+                                ForestChild::Nonterminal(child)
+                            }
+                        })
+                        .collect();
+
+                    packed.push(PackedNode {
+                        production: rule.production,
+                        expression: rule.expression,
+                        children,
+                    });
+                }
+            }
+
+            nodes[node].packed = packed;
+        }
+
+        ParseForest::from_parts(nodes)
+    }
+
+    /// Build one `ParseTree` for `lhs` spanning `start..end`.
+    ///
+    /// `active` holds the (lhs, start, end) triples currently being built, so
+    /// cyclic derivations (e.g. `<a> ::= <a>`) are never followed.
+    fn tree(
+        &self,
+        lhs: &'gram str,
+        start: usize,
+        end: usize,
+        active: &mut HashSet<(&'gram str, usize, usize)>,
+    ) -> Option<ParseTree<'gram>> {
+        if !active.insert((lhs, start, end)) {
+            return None;
+        }
+
+        let found = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+            let candidates: Vec<usize> = self
+                .origins(lhs, end)
+                .filter(|&(origin, _)| origin == start)
+                .map(|(_, rule)| rule)
+                .collect();
+
+            for rule_index in candidates {
+                let rule = &self.rules[rule_index];
+                let mut rhs = vec![];
+                if self.children(rule_index, rule.terms.len(), start, end, active, &mut rhs) {
+                    rhs.reverse();
+                    return Some(ParseTree::new(
+                        rule.production,
+                        rule.expression,
+                        rhs,
+                        start..end,
+                    ));
+                }
+            }
+            None
+        });
+
+        active.remove(&(lhs, start, end));
+        found
+    }
+
+    /// Walk a rule's terms right to left, using the recorded Earley items to
+    /// find where each term began, pushing child nodes in reverse order.
+    fn children(
+        &self,
+        rule_index: usize,
+        dot: usize,
+        start: usize,
+        end: usize,
+        active: &mut HashSet<(&'gram str, usize, usize)>,
+        rhs: &mut Vec<ParseTreeNode<'gram>>,
+    ) -> bool {
+        if dot == 0 {
+            return start == end;
+        }
+
+        let rule = &self.rules[rule_index];
+        let prefix = Item {
+            rule: rule_index,
+            dot: dot - 1,
+            origin: start,
+        };
+
+        match *rule.terms[dot - 1] {
+            Term::Terminal(ref t) => {
+                if end < t.len() || !self.input[..end].ends_with(t.as_str()) {
+                    return false;
+                }
+                let begin = end - t.len();
+                if !self.items[begin].contains(&prefix) {
+                    return false;
+                }
+                rhs.push(ParseTreeNode::Terminal(t.as_str(), begin..end));
+                if self.children(rule_index, dot - 1, start, begin, active, rhs) {
+                    return true;
+                }
+                rhs.pop();
+                false
+            }
+            Term::Nonterminal(ref nt) => {
+                let mut begins: Vec<usize> = self
+                    .origins(nt, end)
+                    .map(|(origin, _)| origin)
+                    .filter(|&origin| origin >= start && self.items[origin].contains(&prefix))
+                    .collect();
+                begins.sort_unstable();
+                begins.dedup();
+
+                for begin in begins {
+                    if let Some(tree) = self.tree(nt, begin, end, active) {
+                        rhs.push(ParseTreeNode::Nonterminal(tree));
+                        if self.children(rule_index, dot - 1, start, begin, active, rhs) {
+                            return true;
+                        }
+                        rhs.pop();
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Walk a rule's terms right to left, using the recorded Earley items to
+    /// find every offset where each term could have begun. Each complete
+    /// walk is pushed to `splits`, with children in reverse order.
+    fn splits(
+        &self,
+        rule_index: usize,
+        dot: usize,
+        start: usize,
+        end: usize,
+        current: &mut Vec<Split<'gram>>,
+        splits: &mut Vec<Vec<Split<'gram>>>,
+    ) {
+        if dot == 0 {
+            if start == end {
+                splits.push(current.clone());
+            }
+            return;
+        }
+
+        let rule = &self.rules[rule_index];
+        let prefix = Item {
+            rule: rule_index,
+            dot: dot - 1,
+            origin: start,
+        };
+
+        match *rule.terms[dot - 1] {
+            Term::Terminal(ref t) => {
+                if end < t.len() || !self.input[..end].ends_with(t.as_str()) {
+                    return;
+                }
+                let begin = end - t.len();
+                if self.items[begin].contains(&prefix) {
+                    current.push(Split::Terminal(t.as_str(), begin..end));
+                    self.splits(rule_index, dot - 1, start, begin, current, splits);
+                    current.pop();
+                }
+            }
+            Term::Nonterminal(ref nt) => {
+                let mut begins: Vec<usize> = self
+                    .origins(nt, end)
+                    .map(|(origin, _)| origin)
+                    .filter(|&origin| origin >= start && self.items[origin].contains(&prefix))
+                    .collect();
+                begins.sort_unstable();
+                begins.dedup();
+
+                for begin in begins {
+                    current.push(Split::Nonterminal((nt.as_str(), begin, end)));
+                    self.splits(rule_index, dot - 1, start, begin, current, splits);
+                    current.pop();
+                }
+            }
+        }
+    }
+}
+
+/// A child found while splitting a rule's span across its terms
+#[derive(Clone)]
+enum Split<'gram> {
+    Terminal(&'gram str, Range<usize>),
+    Nonterminal((&'gram str, usize, usize)),
+}
+
+/// Recognize all of `input` as `start`, returning the chart and the name of
+/// `start` as the grammar holds it
+fn recognize<'gram, 'input>(
+    grammar: &'gram Grammar,
+    start: &str,
+    input: &'input str,
+) -> Result<(Chart<'gram, 'input>, &'gram str), Error> {
+    let start = grammar
+        .productions_iter()
+        .filter_map(|production| match production.lhs {
+            Term::Nonterminal(ref nt) if nt == start => Some(nt.as_str()),
+            _ => None,
+        })
+        .next()
+        .ok_or_else(|| {
+            Error::ParseInputError(format!("No production found for start <{}>!", start))
+        })?;
+
+    let mut chart = Chart::new(grammar, input);
+    chart.recognize(start);
+
+    if !chart.accepts(start) {
+        return Err(Error::ParseInputError(format!(
+            "Failed to parse input as <{}>, no match past byte offset {}!",
+            start,
+            chart.furthest_offset()
+        )));
+    }
+
+    Ok((chart, start))
+}
+
+/// Parse all of `input` as `start`, building the forest of all derivations
+pub(crate) fn parse_forest<'gram>(
+    grammar: &'gram Grammar,
+    start: &str,
+    input: &str,
+) -> Result<ParseForest<'gram>, Error> {
+    let (chart, start) = recognize(grammar, start, input)?;
+    Ok(chart.forest(start))
+}
+
+/// Parse all of `input` as `start` and return the first derivation found,
+/// read straight from the chart without building the whole forest
+pub(crate) fn parse<'gram>(
+    grammar: &'gram Grammar,
+    start: &str,
+    input: &str,
+) -> Result<ParseTree<'gram>, Error> {
+    let (chart, start) = recognize(grammar, start, input)?;
+
+    // an accepted input always has a derivation free of cycles
+    Ok(chart
+        .tree(start, 0, input.len(), &mut HashSet::new())
+        .expect("accepted input should have a parse tree"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dna_grammar() -> Grammar {
+        "<dna> ::= <base> | <base> <dna>
+        <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap()
+    }
+
+    fn leaves<'gram>(tree: &ParseTree<'gram>, into: &mut Vec<&'gram str>) {
+        for node in tree.rhs_iter() {
+            match *node {
+                ParseTreeNode::Terminal(t, _) => into.push(t),
+                ParseTreeNode::Nonterminal(ref child) => leaves(child, into),
+            }
+        }
+    }
+
+    #[test]
+    fn recognizes_dna() {
+        let grammar = dna_grammar();
+        let tree = parse(&grammar, "dna", "GATTACA").unwrap();
+        let mut found = vec![];
+        leaves(&tree, &mut found);
+
+        assert_eq!(found.concat(), "GATTACA");
+        assert_eq!(tree.span(), 0..7);
+        assert_eq!(tree.lhs, &Term::Nonterminal(String::from("dna")));
+    }
+
+    #[test]
+    fn rejects_outside_language() {
+        let grammar = dna_grammar();
+        let result = parse(&grammar, "dna", "GATXACA");
+        match result {
+            Err(Error::ParseInputError(ref s)) => assert!(s.contains("byte offset 3"), "{}", s),
+            e => panic!("should be Error::ParseInputError: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn rejects_empty_input_without_empty_terminal() {
+        let grammar = dna_grammar();
+        assert!(parse(&grammar, "dna", "").is_err());
+    }
+
+    #[test]
+    fn left_recursion() {
+        let grammar: Grammar = "<sum> ::= <sum> \"+\" <num> | <num>
+            <num> ::= \"1\" | \"2\""
+            .parse()
+            .unwrap();
+        let tree = parse(&grammar, "sum", "1+2+1").unwrap();
+
+        // left recursive derivation nests to the left
+        let first = tree.rhs_iter().next().unwrap();
+        assert_eq!(first.span(), 0..3);
+        assert_eq!(tree.rhs_iter().count(), 3);
+    }
+
+    #[test]
+    fn empty_terminal_and_nullable_nonterminals() {
+        let grammar: Grammar = "<s> ::= <opt> <opt> \"x\" <opt>
+            <opt> ::= \"\" | \"y\""
+            .parse()
+            .unwrap();
+
+        assert!(parse(&grammar, "s", "x").is_ok());
+        assert!(parse(&grammar, "s", "yx").is_ok());
+        assert!(parse(&grammar, "s", "yxy").is_ok());
+        assert!(parse(&grammar, "s", "yyyx").is_err());
+    }
+
+    #[test]
+    fn multi_character_terminals() {
+        let grammar: Grammar = "<greeting> ::= \"hello\" \" \" <who>
+            <who> ::= \"world\" | \"wor\" \"ld!\""
+            .parse()
+            .unwrap();
+        let tree = parse(&grammar, "greeting", "hello world!").unwrap();
+        let who = tree.rhs_iter().nth(2).unwrap();
+
+        assert_eq!(who.span(), 6..12);
+    }
+
+    #[test]
+    fn cyclic_grammar() {
+        let grammar: Grammar = "<a> ::= <a> | \"x\"".parse().unwrap();
+        let tree = parse(&grammar, "a", "x").unwrap();
+
+        assert_eq!(tree.rhs_iter().count(), 1);
+    }
+
+    #[test]
+    fn unknown_start() {
+        let grammar = dna_grammar();
+        assert!(parse(&grammar, "rna", "A").is_err());
+    }
+
+    #[test]
+    fn deep_input() {
+        let grammar: Grammar = "<dna> ::= <base> | <dna> <base>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let input = "GATTACA".repeat(5_000);
+
+        let tree = parse(&grammar, "dna", &input).unwrap();
+        let mut found = vec![];
+        let mut pending = vec![tree.rhs_iter()];
+        while let Some(nodes) = pending.last_mut() {
+            match nodes.next() {
+                Some(&ParseTreeNode::Terminal(t, _)) => found.push(t),
+                Some(ParseTreeNode::Nonterminal(child)) => pending.push(child.rhs_iter()),
+                None => {
+                    pending.pop();
+                }
+            }
+        }
+        assert_eq!(found.concat(), input);
+
+        let forest = parse_forest(&grammar, "dna", &input).unwrap();
+        assert_eq!(forest.count(), Some(1));
+        assert_eq!(
+            forest.trees().next().map(|tree| tree.span()),
+            Some(0..input.len())
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! ISO/IEC 14977 EBNF front end.
+//!
+//! EBNF is parsed into a small syntax tree and then desugared into the BNF
+//! model: `[ ]` options, `{ }` repetitions and `( )` groups with several
+//! alternatives each become a fresh nonterminal, named after the rule they
+//! appear in.
+
+use error::{Error, ParseError};
+use expression::Expression;
+use fresh::FreshNames;
+use grammar::Grammar;
+use production::Production;
+use term::Term;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until},
+    character::complete::{self, alpha1, alphanumeric1, anychar, digit1, multispace1, one_of},
+    combinator::{all_consuming, map, map_res, not, opt, peek, recognize},
+    error::VerboseError,
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// Alternatives, each a sequence of factors
+type Definitions = Vec<Vec<Factor>>;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Primary {
+    Optional(Definitions),
+    Repeated(Definitions),
+    Grouped(Definitions),
+    Identifier(String),
+    Terminal(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Factor {
+    count: usize,
+    primary: Primary,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    name: String,
+    definitions: Definitions,
+}
+
+fn comment(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    recognize(delimited(
+        tag("(*"),
+        many0(alt((
+            comment,
+            recognize(preceded(not(alt((tag("*)"), tag("(*")))), anychar)),
+        ))),
+        tag("*)"),
+    ))(input)
+}
+
+/// Skip whitespace and comments
+fn gap(input: &str) -> IResult<&str, (), VerboseError<&str>> {
+    let (input, _) = many0(alt((multispace1, comment)))(input)?;
+
+    Ok((input, ()))
+}
+
+fn symbol<'a>(c: char) -> impl Fn(&'a str) -> IResult<&'a str, char, VerboseError<&'a str>> {
+    terminated(complete::char(c), gap)
+}
+
+/// Meta identifiers may contain spaces, which are collapsed to one
+fn identifier(input: &str) -> IResult<&str, String, VerboseError<&str>> {
+    let (input, name) = recognize(pair(
+        alpha1,
+        many0(alt((
+            alphanumeric1,
+            tag("_"),
+            tag("-"),
+            recognize(pair(complete::space1, peek(alphanumeric1))),
+        ))),
+    ))(input)?;
+    let (input, _) = gap(input)?;
+
+    Ok((input, name.split_whitespace().collect::<Vec<_>>().join(" ")))
+}
+
+fn terminal(input: &str) -> IResult<&str, String, VerboseError<&str>> {
+    let (input, t) = terminated(
+        alt((
+            delimited(complete::char('\''), take_until("'"), complete::char('\'')),
+            delimited(complete::char('"'), take_until("\""), complete::char('"')),
+        )),
+        gap,
+    )(input)?;
+
+    Ok((input, t.to_string()))
+}
+
+fn primary(input: &str) -> IResult<&str, Primary, VerboseError<&str>> {
+    alt((
+        map(
+            delimited(symbol('['), definitions, symbol(']')),
+            Primary::Optional,
+        ),
+        map(
+            delimited(symbol('{'), definitions, symbol('}')),
+            Primary::Repeated,
+        ),
+        map(
+            delimited(symbol('('), definitions, symbol(')')),
+            Primary::Grouped,
+        ),
+        map(terminal, Primary::Terminal),
+        map(identifier, Primary::Identifier),
+    ))(input)
+}
+
+fn factor(input: &str) -> IResult<&str, Factor, VerboseError<&str>> {
+    let (input, count) = opt(terminated(
+        map_res(terminated(digit1, gap), str::parse::<usize>),
+        symbol('*'),
+    ))(input)?;
+    let (input, primary) = primary(input)?;
+
+    Ok((
+        input,
+        Factor {
+            count: count.unwrap_or(1),
+            primary,
+        },
+    ))
+}
+
+/// A single definition, where empty factors are allowed between commas
+fn definition(input: &str) -> IResult<&str, Vec<Factor>, VerboseError<&str>> {
+    let (input, (first, rest)) =
+        pair(opt(factor), many0(preceded(symbol(','), opt(factor))))(input)?;
+
+    Ok((
+        input,
+        first
+            .into_iter()
+            .chain(rest.into_iter().flatten())
+            .collect(),
+    ))
+}
+
+fn definitions(input: &str) -> IResult<&str, Definitions, VerboseError<&str>> {
+    let (input, (first, mut rest)) = pair(
+        definition,
+        many0(preceded(terminated(one_of("|/!"), gap), definition)),
+    )(input)?;
+    rest.insert(0, first);
+
+    Ok((input, rest))
+}
+
+fn rule(input: &str) -> IResult<&str, Rule, VerboseError<&str>> {
+    let (input, (name, _, definitions, _)) = tuple((
+        identifier,
+        symbol('='),
+        definitions,
+        terminated(one_of(";."), gap),
+    ))(input)?;
+
+    Ok((input, Rule { name, definitions }))
+}
+
+fn syntax(input: &str) -> IResult<&str, Vec<Rule>, VerboseError<&str>> {
+    all_consuming(preceded(gap, many1(rule)))(input)
+}
+
+/// Collect every identifier the definitions refer to, however nested
+fn identifiers(definitions: &[Vec<Factor>], into: &mut Vec<String>) {
+    for factor in definitions.iter().flatten() {
+        match factor.primary {
+            Primary::Identifier(ref name) => into.push(name.clone()),
+            Primary::Terminal(_) => {}
+            Primary::Optional(ref nested)
+            | Primary::Repeated(ref nested)
+            | Primary::Grouped(ref nested) => identifiers(nested, into),
+        }
+    }
+}
+
+/// Lowers EBNF rules into `Production`s
+struct Desugar {
+    names: FreshNames,
+    productions: Vec<Production>,
+}
+
+impl Desugar {
+    fn expressions(&mut self, rule: &str, definitions: &[Vec<Factor>]) -> Vec<Expression> {
+        definitions
+            .iter()
+            .map(|definition| self.expression(rule, definition))
+            .collect()
+    }
+
+    fn expression(&mut self, rule: &str, definition: &[Factor]) -> Expression {
+        let mut terms = vec![];
+        for factor in definition {
+            let primary = self.terms(rule, &factor.primary);
+            for _ in 0..factor.count {
+                terms.extend(primary.iter().cloned());
+            }
+        }
+
+        // empty sequences only need spelling out when nothing else remains
+        terms.retain(|term| *term != Term::Terminal(String::new()));
+        if terms.is_empty() {
+            terms.push(Term::Terminal(String::new()));
+        }
+        Expression::from_parts(terms)
+    }
+
+    fn terms(&mut self, rule: &str, primary: &Primary) -> Vec<Term> {
+        match *primary {
+            Primary::Identifier(ref name) => vec![Term::Nonterminal(name.clone())],
+            Primary::Terminal(ref t) => vec![Term::Terminal(t.clone())],
+            Primary::Grouped(ref definitions) if definitions.len() == 1 => self
+                .expression(rule, &definitions[0])
+                .terms_iter()
+                .cloned()
+                .collect(),
+            Primary::Grouped(ref definitions) => {
+                let name = self.names.fresh(&format!("{}-group", rule));
+                let rhs = self.expressions(rule, definitions);
+                vec![self.define(name, rhs)]
+            }
+            Primary::Optional(ref definitions) => {
+                let name = self.names.fresh(&format!("{}-opt", rule));
+                let mut rhs = self.expressions(rule, definitions);
+                rhs.push(Expression::from_parts(vec![Term::Terminal(String::new())]));
+                vec![self.define(name, rhs)]
+            }
+            Primary::Repeated(ref definitions) => {
+                let name = self.names.fresh(&format!("{}-rep", rule));
+                let repeat = Term::Nonterminal(name.clone());
+                let mut rhs = vec![Expression::from_parts(vec![Term::Terminal(String::new())])];
+                for mut expression in self.expressions(rule, definitions) {
+                    expression.add_term(repeat.clone());
+                    rhs.push(expression);
+                }
+                vec![self.define(name, rhs)]
+            }
+        }
+    }
+
+    fn define(&mut self, name: String, rhs: Vec<Expression>) -> Term {
+        let lhs = Term::Nonterminal(name);
+        self.productions
+            .push(Production::from_parts(lhs.clone(), rhs));
+        lhs
+    }
+}
+
+/// Parse ISO 14977 EBNF into a `Grammar`
+pub(crate) fn parse(input: &str) -> Result<Grammar, Error> {
+    let rules = match syntax(input) {
+        Result::Ok((_, rules)) => rules,
+        Result::Err(e) => return Err(Error::from(ParseError::from_nom(input, e))),
+    };
+
+    // fresh names must not capture references to rules left undefined
+    let mut used = vec![];
+    for rule in &rules {
+        used.push(rule.name.clone());
+        identifiers(&rule.definitions, &mut used);
+    }
+    let mut desugar = Desugar {
+        names: FreshNames::new(used),
+        productions: vec![],
+    };
+
+    let mut productions = vec![];
+    for rule in &rules {
+        let rhs = desugar.expressions(&rule.name, &rule.definitions);
+        productions.push(Production::from_parts(
+            Term::Nonterminal(rule.name.clone()),
+            rhs,
+        ));
+        productions.append(&mut desugar.productions);
+    }
+
+    Ok(Grammar::from_parts(productions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bnf(input: &str) -> Grammar {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn concatenation_and_alternation() {
+        let grammar = parse("digit = '0' | '1' ; pair = digit, digit ;").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<digit> ::= \"0\" | \"1\"
+                <pair> ::= <digit> <digit>")
+        );
+    }
+
+    #[test]
+    fn alternative_separators_and_terminators() {
+        let grammar = parse("a = \"x\" / \"y\" ! \"z\" .").unwrap();
+        assert_eq!(grammar, bnf("<a> ::= \"x\" | \"y\" | \"z\""));
+    }
+
+    #[test]
+    fn optional() {
+        let grammar = parse("signed = ['-'], digit;").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<signed> ::= <signed-opt> <digit>
+                <signed-opt> ::= \"-\" | \"\"")
+        );
+    }
+
+    #[test]
+    fn repetition() {
+        let grammar = parse("digits = digit, {digit};").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<digits> ::= <digit> <digits-rep>
+                <digits-rep> ::= \"\" | <digit> <digits-rep>")
+        );
+    }
+
+    #[test]
+    fn grouping() {
+        let grammar = parse("a = ('x', 'y'), ('z' | 'w');").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<a> ::= \"x\" \"y\" <a-group>
+                <a-group> ::= \"z\" | \"w\"")
+        );
+    }
+
+    #[test]
+    fn fresh_names_avoid_rules() {
+        let grammar = parse("a = ['x'], ['y']; a-opt = 'z';").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<a> ::= <a-opt-2> <a-opt-3>
+                <a-opt-2> ::= \"x\" | \"\"
+                <a-opt-3> ::= \"y\" | \"\"
+                <a-opt> ::= \"z\"")
+        );
+    }
+
+    #[test]
+    fn fresh_names_avoid_references() {
+        let grammar = parse("digits = digit, {digit}, [digits-rep];").unwrap();
+        assert_eq!(
+            grammar,
+            bnf("<digits> ::= <digit> <digits-rep-2> <digits-opt>
+                <digits-rep-2> ::= \"\" | <digit> <digits-rep-2>
+                <digits-opt> ::= <digits-rep> | \"\"")
+        );
+    }
+
+    #[test]
+    fn repeat_count() {
+        let grammar = parse("triple = 3 * 'a';").unwrap();
+        assert_eq!(grammar, bnf("<triple> ::= \"a\" \"a\" \"a\""));
+    }
+
+    #[test]
+    fn empty_sequence() {
+        let grammar = parse("maybe = 'x' | ;").unwrap();
+        assert_eq!(grammar, bnf("<maybe> ::= \"x\" | \"\""));
+    }
+
+    #[test]
+    fn comments_and_spaced_identifiers() {
+        let grammar = parse(
+            "(* a (* nested *) comment *)
+            syntax rule = meta  identifier, '=' (* between *) ;",
+        )
+        .unwrap();
+        assert_eq!(grammar, bnf("<syntax rule> ::= <meta identifier> \"=\""));
+    }
+
+    #[test]
+    fn quotes_in_terminals() {
+        let grammar = parse("q = \"'\" | '\"';").unwrap();
+        assert_eq!(grammar, bnf("<q> ::= \"'\" | '\"'"));
+    }
+
+    #[test]
+    fn unsupported_exception() {
+        assert!(parse("letter = character - digit;").is_err());
+    }
+
+    #[test]
+    fn missing_terminator() {
+        let result = parse("a = 'x'");
+        match result {
+            Err(Error::ParseError(_)) => (),
+            e => panic!("should be Error::ParseError: {:?}", e),
+        }
+    }
+}
@@ -1,25 +1,51 @@
+use span::{LineIndex, Position};
 use std::error;
 use std::fmt;
 use std::str;
 
 use nom::{
-    error::{VerboseError, VerboseErrorKind},
+    error::{ErrorKind, VerboseError, VerboseErrorKind},
     Err,
 };
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Error {
-    ParseError(String),
+    ParseError(ParseError),
     GenerateError(String),
     RecursionLimit(String),
+    ParseInputError(String),
+    /// Nonterminals reachable from the start symbol which can never derive a
+    /// string of terminals, so generation could not finish
+    Unproductive(Vec<String>),
+    /// A nonterminal defined by more than one production, where that is
+    /// not allowed
+    DuplicateProduction(String),
+    /// A nonterminal to generate from which has no production
+    UndefinedNonterminal(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::ParseError(ref s) => write!(f, "{}", s),
+            Error::ParseError(ref e) => write!(f, "{}", e),
             Error::GenerateError(ref s) => write!(f, "{}", s),
             Error::RecursionLimit(ref s) => write!(f, "{}", s),
+            Error::ParseInputError(ref s) => write!(f, "{}", s),
+            Error::Unproductive(ref nonterminals) => {
+                let names = nonterminals
+                    .iter()
+                    .map(|nt| format!("<{}>", nt))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "Cannot generate a sentence, no string of terminals derives from {}",
+                    names.join(", ")
+                )
+            }
+            Error::DuplicateProduction(ref nt) => {
+                write!(f, "<{}> is defined by more than one production", nt)
+            }
+            Error::UndefinedNonterminal(ref nt) => write!(f, "<{}> has no production", nt),
         }
     }
 }
@@ -30,30 +56,126 @@ impl error::Error for Error {
     }
 }
 
-impl<'a> From<VerboseError<(&'a str, VerboseErrorKind)>> for Error {
-    fn from(err: VerboseError<(&str, VerboseErrorKind)>) -> Self {
-        Error::ParseError(format!("Parsing error: {:?}", err))
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::ParseError(err)
     }
 }
 
-impl<'a> From<Err<VerboseError<&str>>> for Error {
-    fn from(err: Err<VerboseError<&str>>) -> Self {
-        Error::ParseError(format!("Parsing error: {:?}", err))
+/// A ParseError describes where text failed to parse, and what was expected there
+///
+/// Its `Display` renders the offending line with a caret under the error:
+///
+/// ```text
+/// 1:11: expected `>` to close nonterminal started at 1:9
+///   |
+/// 1 | <a> ::= <b
+///   |           ^
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub position: Position,
+    /// The line of source text containing `position`
+    pub snippet: String,
+    /// What was expected at `position`
+    pub expected: String,
+}
+
+impl ParseError {
+    /// Construct a `ParseError` at `offset` into `source`
+    pub(crate) fn new(source: &str, offset: usize, expected: String) -> ParseError {
+        let position = LineIndex::new(source).position(offset);
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let snippet = source[line_start..]
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string();
+
+        ParseError {
+            position,
+            snippet,
+            expected,
+        }
+    }
+
+    /// Describe a nom error from parsing `source`
+    pub(crate) fn from_nom(source: &str, err: Err<VerboseError<&str>>) -> ParseError {
+        let errors = match err {
+            Err::Error(e) | Err::Failure(e) => e.errors,
+            Err::Incomplete(_) => {
+                return ParseError::new(
+                    source,
+                    source.len(),
+                    String::from("unexpected end of input"),
+                )
+            }
+        };
+        let offset = |rest: &str| source.len() - rest.len();
+
+        let (rest, kind) = match errors.first() {
+            Some(&(rest, ref kind)) => (rest, kind),
+            None => return ParseError::new(source, 0, String::from("invalid syntax")),
+        };
+        let at = offset(rest);
+        let context = errors.iter().find_map(|&(rest, ref kind)| match *kind {
+            VerboseErrorKind::Context(context) => Some((offset(rest), context)),
+            _ => None,
+        });
+
+        let expected = match context {
+            // mention where the construct began when other text came between
+            Some((start, context)) if start < at && !source[start..at].trim().is_empty() => {
+                let started = LineIndex::new(source).position(start);
+                format!("{} started at {}", context, started)
+            }
+            Some((_, context)) => context.to_string(),
+            None => describe(kind, rest),
+        };
+
+        ParseError::new(source, at, expected)
     }
 }
 
-impl<'a> From<(&'a str, VerboseErrorKind)> for Error {
-    fn from(err: (&str, VerboseErrorKind)) -> Self {
-        let string = format!("Parsing error: {:?}\n {:?}", err.1, err.0);
-        Error::ParseError(string)
+fn describe(kind: &VerboseErrorKind, rest: &str) -> String {
+    match *kind {
+        VerboseErrorKind::Context(context) => context.to_string(),
+        VerboseErrorKind::Char(c) => format!("expected `{}`", c),
+        VerboseErrorKind::Nom(ErrorKind::Escaped) => String::from("invalid escape sequence"),
+        VerboseErrorKind::Nom(_) if rest.is_empty() => String::from("unexpected end of input"),
+        VerboseErrorKind::Nom(ErrorKind::Eof) => String::from("expected end of input"),
+        VerboseErrorKind::Nom(_) => String::from("unexpected text"),
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line = self.position.line.to_string();
+        let gutter = " ".repeat(line.len());
+
+        writeln!(f, "{}: {}", self.position, self.expected)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line, self.snippet)?;
+        write!(
+            f,
+            "{} | {}^",
+            gutter,
+            " ".repeat(self.position.column.saturating_sub(1))
+        )
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_late_init, clippy::unnecessary_literal_unwrap)]
 mod tests {
-    use error::Error;
+    use error::{Error, ParseError};
     use nom::{bytes::complete::tag, error::VerboseError, Err, IResult};
 
+    #[allow(
+        unknown_lints,
+        clippy::needless_lifetimes,
+        mismatched_lifetime_syntaxes
+    )]
     fn give_error_kind<'a>(input: &'a str) -> IResult<&'a str, &str, VerboseError<&'a str>> {
         let (input, _) = tag("1234")(input)?;
         let (input, res) = tag("5678")(input)?;
@@ -72,7 +194,8 @@ mod tests {
             _ => panic!("gets_error_error should result in IResult::Err"),
         }
 
-        let bnf_error: Result<String, Error> = Err(Error::from(nom_error));
+        let bnf_error: Result<String, Error> =
+            Err(Error::from(ParseError::from_nom("12340", nom_error)));
 
         assert!(
             bnf_error.is_err(),
@@ -81,7 +204,7 @@ mod tests {
         );
 
         match bnf_error.unwrap_err() {
-            Error::ParseError(_) => (),
+            Error::ParseError(ref e) => assert_eq!(e.position.offset, 4),
             e => panic!("production error should be error parsing: {:?}", e),
         }
     }
@@ -95,7 +218,8 @@ mod tests {
             _ => panic!("gets_error_error should result in IResult::Err"),
         }
 
-        let bnf_error: Result<String, Error> = Err(Error::from(nom_error));
+        let bnf_error: Result<String, Error> =
+            Err(Error::from(ParseError::from_nom("", nom_error)));
 
         assert!(
             bnf_error.is_err(),
@@ -103,7 +227,7 @@ mod tests {
             bnf_error
         );
         match bnf_error.unwrap_err() {
-            Error::ParseError(_) => (),
+            Error::ParseError(ref e) => assert_eq!(e.expected, "unexpected end of input"),
             e => panic!("production error should be parse error: {:?}", e),
         }
     }
@@ -128,11 +252,21 @@ mod tests {
 
     #[test]
     fn test_error_display() {
-        let parse_error = Error::ParseError(String::from("parsing error!"));
+        let parse_error = Error::from(ParseError::new(
+            "<a> ::= \"x\"\n<b> ::= <a",
+            21,
+            String::from("parsing error!"),
+        ));
         let generate_error = Error::GenerateError(String::from("error generating!"));
         let recursion_error = Error::RecursionLimit(String::from("recursion limit reached!"));
+        let unproductive_error = Error::Unproductive(vec![String::from("a"), String::from("b")]);
+        let duplicate_error = Error::DuplicateProduction(String::from("a"));
+        let undefined_error = Error::UndefinedNonterminal(String::from("a"));
 
-        assert_eq!(parse_error.to_string(), String::from("parsing error!"));
+        assert_eq!(
+            parse_error.to_string(),
+            "2:10: parsing error!\n  |\n2 | <b> ::= <a\n  |          ^"
+        );
         assert_eq!(
             generate_error.to_string(),
             String::from("error generating!")
@@ -141,5 +275,25 @@ mod tests {
             recursion_error.to_string(),
             String::from("recursion limit reached!")
         );
+        assert_eq!(
+            unproductive_error.to_string(),
+            "Cannot generate a sentence, no string of terminals derives from <a>, <b>"
+        );
+        assert_eq!(
+            duplicate_error.to_string(),
+            "<a> is defined by more than one production"
+        );
+        assert_eq!(undefined_error.to_string(), "<a> has no production");
+    }
+
+    #[test]
+    fn display_column_zero() {
+        let mut parse_error = ParseError::new("<a> ::= <", 9, String::from("parsing error!"));
+        parse_error.position.column = 0;
+
+        assert_eq!(
+            parse_error.to_string(),
+            "1:0: parsing error!\n  |\n1 | <a> ::= <\n  | ^"
+        );
     }
 }
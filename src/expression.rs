@@ -1,30 +1,102 @@
-use error::Error;
+use error::{Error, ParseError};
 use parsers;
+use span::{LineIndex, Span};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::slice;
 use std::str::FromStr;
 use term::Term;
 
 /// An Expression is comprised of any number of Terms
-#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+///
+/// Expressions parsed from text remember where they and their terms came
+/// from, but those spans take no part in comparing or hashing expressions.
+#[derive(Clone, Debug, Default)]
 pub struct Expression {
     terms: Vec<Term>,
+    span: Option<Span>,
+    term_spans: Vec<Option<Span>>,
 }
 
 impl Expression {
     /// Construct a new `Expression`
     pub fn new() -> Expression {
-        Expression { terms: vec![] }
+        Expression {
+            terms: vec![],
+            span: None,
+            term_spans: vec![],
+        }
     }
 
     /// Construct an `Expression` from `Term`s
     pub fn from_parts(v: Vec<Term>) -> Expression {
-        Expression { terms: v }
+        Expression {
+            term_spans: vec![None; v.len()],
+            terms: v,
+            span: None,
+        }
+    }
+
+    /// Construct an `Expression` from parsed `Term`s and their provisional spans
+    pub(crate) fn from_spanned_parts(terms: Vec<(Term, Span)>) -> Expression {
+        let span = match (terms.first(), terms.last()) {
+            (Some(&(_, first)), Some(&(_, last))) => Some(Span {
+                start: first.start,
+                end: last.end,
+            }),
+            _ => None,
+        };
+        let (terms, term_spans) = terms
+            .into_iter()
+            .map(|(term, span)| (term, Some(span)))
+            .unzip();
+
+        Expression {
+            terms,
+            span,
+            term_spans,
+        }
+    }
+
+    /// Resolve provisional spans against the source they were parsed from
+    pub(crate) fn locate(&mut self, lines: &LineIndex) {
+        for span in self
+            .span
+            .iter_mut()
+            .chain(self.term_spans.iter_mut().flatten())
+        {
+            span.locate(lines);
+        }
+    }
+
+    /// Get the source span of the `Expression`, if it was parsed from text
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Get the source span of the `Term` at `index`, if it was parsed from text
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate bnf;
+    /// use bnf::Expression;
+    ///
+    /// fn main() {
+    ///     let expression: Expression = "<base> \"A\"".parse().unwrap();
+    ///     let span = expression.term_span(1).unwrap();
+    ///
+    ///     assert_eq!((span.start.column, span.end.column), (8, 11));
+    /// }
+    /// ```
+    pub fn term_span(&self, index: usize) -> Option<Span> {
+        self.term_spans.get(index).and_then(|&span| span)
     }
 
     /// Add `Term` to `Expression`
     pub fn add_term(&mut self, term: Term) {
-        self.terms.push(term)
+        self.terms.push(term);
+        self.term_spans.push(None);
     }
 
     /// Remove `Term` from `Expression`
@@ -52,6 +124,7 @@ impl Expression {
     /// ```
     pub fn remove_term(&mut self, term: &Term) -> Option<Term> {
         if let Some(pos) = self.terms.iter().position(|x| *x == *term) {
+            self.term_spans.remove(pos);
             Some(self.terms.remove(pos))
         } else {
             None
@@ -59,6 +132,7 @@ impl Expression {
     }
 
     /// Get iterator of `Term`s within `Expression`
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     pub fn terms_iter(&self) -> Iter {
         Iter {
             iterator: self.terms.iter(),
@@ -66,6 +140,7 @@ impl Expression {
     }
 
     /// Get mutable iterator of `Term`s within `Expression`
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     pub fn terms_iter_mut(&mut self) -> IterMut {
         IterMut {
             iterator: self.terms.iter_mut(),
@@ -73,6 +148,20 @@ impl Expression {
     }
 }
 
+impl PartialEq for Expression {
+    fn eq(&self, other: &Expression) -> bool {
+        self.terms == other.terms
+    }
+}
+
+impl Eq for Expression {}
+
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.terms.hash(state);
+    }
+}
+
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let display = self
@@ -92,7 +181,7 @@ impl FromStr for Expression {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match parsers::expression_complete(s) {
             Result::Ok((_, o)) => Ok(o),
-            Result::Err(e) => Err(Error::from(e)),
+            Result::Err(e) => Err(Error::from(ParseError::from_nom(s, e))),
         }
     }
 }
@@ -135,7 +224,7 @@ mod tests {
             if terms.is_empty() {
                 terms.push(Term::arbitrary(g));
             }
-            Expression { terms }
+            Expression::from_parts(terms)
         }
     }
 
@@ -0,0 +1,45 @@
+use std::collections::HashSet;
+
+/// Hands out nonterminal names which collide with no name already in use.
+pub(crate) struct FreshNames {
+    used: HashSet<String>,
+}
+
+impl FreshNames {
+    /// Construct `FreshNames` avoiding the given names
+    pub(crate) fn new<I: IntoIterator<Item = String>>(used: I) -> FreshNames {
+        FreshNames {
+            used: used.into_iter().collect(),
+        }
+    }
+
+    /// Get an unused name, `base` itself if possible or else `base` with the
+    /// smallest numeric suffix that is free, and mark it as used
+    pub(crate) fn fresh(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut suffix = 1;
+        while self.used.contains(&name) {
+            suffix += 1;
+            name = format!("{}-{}", base, suffix);
+        }
+        self.used.insert(name.clone());
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avoids_used_names() {
+        let used = vec!["a", "a-tail", "a-tail-2", "b"];
+        let mut names = FreshNames::new(used.into_iter().map(String::from));
+
+        assert_eq!(names.fresh("a-tail"), "a-tail-3");
+        assert_eq!(names.fresh("a-tail"), "a-tail-4");
+        assert_eq!(names.fresh("c"), "c");
+        assert_eq!(names.fresh("c"), "c-2");
+        assert_eq!(names.fresh("b"), "b-2");
+    }
+}
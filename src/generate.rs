@@ -0,0 +1,392 @@
+use analysis;
+use compiled::{CompiledGrammar, Symbol};
+use error::Error;
+use grammar::Grammar;
+use rand::Rng;
+use stacker;
+use term::Term;
+
+/// What generation does on reaching a nonterminal without any production
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UndefinedPolicy {
+    /// Write the nonterminal out as is, i.e. `<identifier>`
+    #[default]
+    Emit,
+    /// Fail with `Error::UndefinedNonterminal`
+    Error,
+    /// Leave it out of the sentence
+    Skip,
+}
+
+/// GenerateOptions configure how a `Grammar` generates random sentences
+///
+/// By default generation starts from the grammar's start symbol, has no
+/// limit on depth or length, and emits undefined nonterminals as is.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate bnf;
+/// extern crate rand;
+/// use bnf::{GenerateOptions, Grammar, UndefinedPolicy};
+///
+/// fn main() {
+///     let grammar: Grammar = "<dna> ::= <base> | <base> <dna> | <rna>
+///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+///         .parse()
+///         .unwrap();
+///     let options = GenerateOptions::new()
+///         .start("dna")
+///         .max_depth(8)
+///         .max_length(100)
+///         .undefined(UndefinedPolicy::Skip);
+///     let sentence = options.generate(&grammar, &mut rand::thread_rng()).unwrap();
+///
+///     assert!(sentence.len() <= 7);
+///     assert!(sentence.chars().all(|c| "ACGT".contains(c)));
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GenerateOptions {
+    start: Option<String>,
+    max_depth: Option<usize>,
+    max_length: Option<usize>,
+    undefined: UndefinedPolicy,
+}
+
+impl GenerateOptions {
+    /// Construct the default `GenerateOptions`
+    pub fn new() -> GenerateOptions {
+        GenerateOptions::default()
+    }
+
+    /// Start from the nonterminal named `start`, rather than the grammar's
+    /// start symbol. It is an error if `start` has no production.
+    pub fn start(mut self, start: &str) -> GenerateOptions {
+        self.start = Some(start.to_string());
+        self
+    }
+
+    /// Expand no more than `max_depth` levels of productions.
+    ///
+    /// Every expansion picks only among alternatives whose shortest
+    /// derivation fits in the depth left, so recursive grammars always
+    /// produce a sentence, and alternatives which can never finish are never
+    /// picked. It is an error when even the shortest derivation of the start
+    /// nonterminal is deeper than `max_depth`.
+    pub fn max_depth(mut self, max_depth: usize) -> GenerateOptions {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Fail as soon as the sentence grows longer than `max_length` bytes
+    pub fn max_length(mut self, max_length: usize) -> GenerateOptions {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Handle nonterminals without any production according to `policy`
+    pub fn undefined(mut self, policy: UndefinedPolicy) -> GenerateOptions {
+        self.undefined = policy;
+        self
+    }
+
+    /// Generate a random sentence from `grammar`, making random choices with
+    /// `rng`
+    pub fn generate<R: Rng + ?Sized>(
+        &self,
+        grammar: &Grammar,
+        rng: &mut R,
+    ) -> Result<String, Error> {
+        let start_rule = match self.start {
+            Some(ref start) => start.as_str(),
+            None => grammar.start_rule(Error::GenerateError)?,
+        };
+        let compiled = grammar.compiled();
+        let start = match compiled.symbols().nonterminal(start_rule) {
+            Some(symbol) if compiled.is_defined(symbol) => symbol,
+            _ => return Err(Error::UndefinedNonterminal(start_rule.to_string())),
+        };
+        let min_depths = analysis::min_depths(compiled);
+
+        match (self.max_depth, min_depths[start.index()]) {
+            (Some(max_depth), Some(min_depth)) if min_depth > max_depth => {
+                return Err(Error::GenerateError(format!(
+                    "<{}> needs a depth of at least {} to generate, more than the maximum of {}!",
+                    start_rule, min_depth, max_depth
+                )))
+            }
+            (Some(_), None) => return Err(Error::Unproductive(vec![start_rule.to_string()])),
+            (Some(_), Some(_)) => {}
+            (None, _) => {
+                // refuse up front rather than recursing until the stack runs out
+                let mut unproductive: Vec<String> = compiled
+                    .reachable(start)
+                    .into_iter()
+                    .filter(|symbol| min_depths[symbol.index()].is_none())
+                    .map(|symbol| name(compiled, symbol))
+                    .collect();
+                unproductive.sort();
+                if !unproductive.is_empty() {
+                    return Err(Error::Unproductive(unproductive));
+                }
+            }
+        }
+
+        let mut generator = Generator {
+            compiled,
+            options: self,
+            min_depths: &min_depths,
+            rng,
+            sentence: String::new(),
+        };
+        generator.traverse(start, self.max_depth)?;
+        Ok(generator.sentence)
+    }
+}
+
+/// The name of a nonterminal, or the text of a terminal
+fn name(compiled: &CompiledGrammar, symbol: Symbol) -> String {
+    match *compiled.symbols().term(symbol) {
+        Term::Nonterminal(ref nt) | Term::Terminal(ref nt) => nt.clone(),
+    }
+}
+
+/// The state of generating one sentence
+struct Generator<'a, R: Rng + ?Sized + 'a> {
+    compiled: &'a CompiledGrammar,
+    options: &'a GenerateOptions,
+    min_depths: &'a [Option<usize>],
+    rng: &'a mut R,
+    sentence: String,
+}
+
+impl<'a, R: Rng + ?Sized> Generator<'a, R> {
+    /// Expand `symbol` onto the end of the sentence, and when `depth` holds
+    /// the depth left, choose only among expressions which can finish within
+    /// that depth
+    fn traverse(&mut self, symbol: Symbol, depth: Option<usize>) -> Result<(), Error> {
+        const STACK_RED_ZONE: usize = 32 * 1024; // 32KB
+                                                 // heavy recursion happening, we've hit out tolerable threshold
+        if let Some(remaining) = stacker::remaining_stack() {
+            if remaining < STACK_RED_ZONE {
+                return Err(Error::RecursionLimit(format!(
+                    "Limit for recursion reached processing {}!",
+                    self.compiled.symbols().term(symbol)
+                )));
+            }
+        }
+
+        if !self.compiled.is_defined(symbol) {
+            return match self.options.undefined {
+                UndefinedPolicy::Emit => {
+                    let text = self.compiled.symbols().term(symbol).to_string();
+                    self.push(&text)
+                }
+                UndefinedPolicy::Error => {
+                    Err(Error::UndefinedNonterminal(name(self.compiled, symbol)))
+                }
+                UndefinedPolicy::Skip => Ok(()),
+            };
+        }
+
+        let compiled = self.compiled;
+        let min_depths = self.min_depths;
+        let expressions = || {
+            compiled
+                .expressions(symbol)
+                .filter(move |expression| match depth {
+                    Some(depth) => analysis::expression_min_depth(min_depths, expression)
+                        .is_some_and(|min_depth| min_depth <= depth),
+                    None => true,
+                })
+        };
+
+        // pick the nth candidate rather than collecting them
+        let count = expressions().count();
+        let expression = match count {
+            0 => None,
+            _ => expressions().nth(choose_index(self.rng, count)),
+        };
+        let expression = match expression {
+            Some(e) => e,
+            None => {
+                return Err(Error::GenerateError(String::from(
+                    "Couldn't select random Expression!",
+                )));
+            }
+        };
+
+        let depth = depth.map(|depth| depth - 1);
+        for &term in expression {
+            match *compiled.symbols().term(term) {
+                Term::Nonterminal(_) => self.traverse(term, depth)?,
+                Term::Terminal(ref t) => self.push(t)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add `text` to the sentence, keeping within the maximum length
+    fn push(&mut self, text: &str) -> Result<(), Error> {
+        self.sentence.push_str(text);
+        match self.options.max_length {
+            Some(max_length) if self.sentence.len() > max_length => {
+                Err(Error::GenerateError(format!(
+                    "Generated sentence is longer than the maximum of {} bytes!",
+                    max_length
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Draw an index below `count` from `rng` exactly as `SliceRandom::choose`
+/// would, which draws a `u32` whenever `count` fits in one
+fn choose_index<R: Rng + ?Sized>(rng: &mut R, count: usize) -> usize {
+    if count <= u32::MAX as usize {
+        rng.gen_range(0, count as u32) as usize
+    } else {
+        rng.gen_range(0, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn rng() -> StdRng {
+        SeedableRng::from_seed([0; 32])
+    }
+
+    #[test]
+    fn undefined_policies() {
+        let grammar: Grammar = "<s> ::= \"a\" <missing> \"b\"".parse().unwrap();
+
+        let emit = GenerateOptions::new();
+        assert_eq!(
+            emit.generate(&grammar, &mut rng()),
+            Ok(String::from("a<missing>b"))
+        );
+
+        let error = GenerateOptions::new().undefined(UndefinedPolicy::Error);
+        assert_eq!(
+            error.generate(&grammar, &mut rng()),
+            Err(Error::UndefinedNonterminal(String::from("missing")))
+        );
+
+        let skip = GenerateOptions::new().undefined(UndefinedPolicy::Skip);
+        assert_eq!(skip.generate(&grammar, &mut rng()), Ok(String::from("ab")));
+    }
+
+    #[test]
+    fn max_length() {
+        let grammar: Grammar = "<s> ::= \"abc\" <s> | \"abc\"".parse().unwrap();
+        let options = GenerateOptions::new().max_length(5);
+        let mut rng = rng();
+
+        for _ in 0..20 {
+            match options.generate(&grammar, &mut rng) {
+                Ok(sentence) => assert_eq!(sentence, "abc"),
+                Err(Error::GenerateError(e)) => assert_eq!(
+                    e,
+                    "Generated sentence is longer than the maximum of 5 bytes!"
+                ),
+                e => panic!("should be Error::GenerateError: {:?}", e),
+            }
+        }
+
+        let options = GenerateOptions::new().max_length(3).max_depth(1);
+        assert_eq!(
+            options.generate(&grammar, &mut rng),
+            Ok(String::from("abc"))
+        );
+    }
+
+    #[test]
+    fn start_and_depth() {
+        let grammar: Grammar = "<a> ::= <b> <b>
+            <b> ::= <c>
+            <c> ::= \"c\" | <c> \"c\""
+            .parse()
+            .unwrap();
+
+        let options = GenerateOptions::new().start("b").max_depth(2);
+        assert_eq!(
+            options.generate(&grammar, &mut rng()),
+            Ok(String::from("c"))
+        );
+
+        let options = GenerateOptions::new().max_depth(2);
+        match options.generate(&grammar, &mut rng()) {
+            Err(Error::GenerateError(ref e)) => assert_eq!(
+                e,
+                "<a> needs a depth of at least 3 to generate, more than the maximum of 2!"
+            ),
+            e => panic!("should be Error::GenerateError: {:?}", e),
+        }
+
+        let options = GenerateOptions::new().start("d");
+        assert_eq!(
+            options.generate(&grammar, &mut rng()),
+            Err(Error::UndefinedNonterminal(String::from("d")))
+        );
+    }
+
+    #[test]
+    fn choose_index_draws_like_choose() {
+        use rand::seq::SliceRandom;
+
+        let items: Vec<usize> = (0..7).collect();
+        let (mut a, mut b) = (rng(), rng());
+        for _ in 0..100 {
+            assert_eq!(Some(&choose_index(&mut a, 7)), items.choose(&mut b));
+        }
+
+        // past u32::MAX a full width draw is needed to reach every index
+        let count = u32::MAX as usize + 2;
+        let (mut a, mut b) = (rng(), rng());
+        for _ in 0..100 {
+            let index = choose_index(&mut a, count);
+            assert!(index < count);
+            assert_eq!(index, b.gen_range(0, count));
+        }
+    }
+
+    #[test]
+    fn seeded_sentences() {
+        // the sentences generated before expressions were picked without
+        // collecting them, which seeded generation must keep reproducing
+        let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\"
+            <base> ::= \"U\""
+            .parse()
+            .unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([7; 32]);
+
+        let unbounded: Vec<_> = (0..20)
+            .map(|_| grammar.generate_seeded(&mut rng).unwrap())
+            .collect();
+        assert_eq!(
+            unbounded,
+            vec![
+                "GU", "G", "T", "A", "A", "UC", "U", "T", "UTGU", "GC", "GC", "C", "G", "G", "U",
+                "C", "ATT", "G", "CGG", "U",
+            ]
+        );
+
+        let bounded: Vec<_> = (0..20)
+            .map(|_| grammar.generate_seeded_bounded(&mut rng, 6).unwrap())
+            .collect();
+        assert_eq!(
+            bounded,
+            vec![
+                "TG", "C", "UT", "AC", "T", "CTC", "G", "TT", "TAA", "CT", "C", "C", "CCT", "G",
+                "UAUA", "TCGG", "GT", "ATC", "G", "AT",
+            ]
+        );
+    }
+}
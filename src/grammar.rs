@@ -1,18 +1,56 @@
-use error::Error;
-use expression::Expression;
+use abnf;
+use analysis::GrammarAnalysis;
+use compiled::CompiledGrammar;
+use earley;
+use ebnf;
+use error::{Error, ParseError};
+use generate::GenerateOptions;
+use ll1::PredictionTable;
+use lr::{LrKind, LrTable};
+use normal_form;
+use parse_forest::ParseForest;
+use parse_tree::ParseTree;
 use parsers;
 use production::Production;
-use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
-use stacker;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use span::LineIndex;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::slice;
 use std::str;
+use std::sync::OnceLock;
 use term::Term;
+use transform;
+use validate::{self, ValidationReport};
 
 /// A Grammar is comprised of any number of Productions
-#[derive(Clone, Default, Debug, Eq, Hash, PartialEq)]
+///
+/// Lookups by nonterminal, generation and analyses go through a
+/// `CompiledGrammar` built on first use, which takes no part in comparing or
+/// hashing grammars, and which a clone builds afresh.
+#[derive(Default)]
 pub struct Grammar {
     productions: Vec<Production>,
+    /// The declared start nonterminal, if any
+    start: Option<String>,
+    /// Built on first use, and dropped whenever productions may have moved
+    /// or been renamed
+    compiled: OnceLock<CompiledGrammar>,
+}
+
+/// How `Grammar::merge_productions` treats a nonterminal defined by more
+/// than one production
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Every later definition's expressions become alternatives of the
+    /// first, except those it already has
+    #[default]
+    Merge,
+    /// A repeated definition is an error
+    Error,
+    /// The last definition replaces the earlier ones
+    LastWins,
 }
 
 impl Grammar {
@@ -20,29 +58,366 @@ impl Grammar {
     pub fn new() -> Grammar {
         Grammar {
             productions: vec![],
+            start: None,
+            compiled: OnceLock::new(),
         }
     }
 
     /// Construct an `Grammar` from `Production`s
     pub fn from_parts(v: Vec<Production>) -> Grammar {
-        Grammar { productions: v }
+        Grammar {
+            productions: v,
+            start: None,
+            compiled: OnceLock::new(),
+        }
+    }
+
+    /// Construct a `Grammar` from BNF text, recovering from malformed productions.
+    ///
+    /// Rather than stopping at the first error, parsing resumes after the next
+    /// `;` or at the next `<name> ::=`. Returns every production which parsed,
+    /// along with an error for each one which did not.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input = "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\" |
+    ///         <rna> ::= <rna-base> | <rna-base> <rna>
+    ///         <rna-base> ::= \"A\" | \"C\" | \"G\" | \"U";
+    ///     let (grammar, errors) = Grammar::from_str_recovering(input);
+    ///
+    ///     assert_eq!(grammar.productions_iter().count(), 2);
+    ///     assert_eq!(errors.len(), 2);
+    ///     assert_eq!(errors[0].position.line, 2);
+    /// }
+    /// ```
+    pub fn from_str_recovering(s: &str) -> (Grammar, Vec<ParseError>) {
+        let lines = LineIndex::new(s);
+        let mut productions: Vec<Production> = vec![];
+        let mut errors = vec![];
+
+        let mut input = s;
+        let mut start = None;
+        let mut leading_comments = vec![];
+        match parsers::start_directive(input) {
+            Ok((rest, (comments, name))) => {
+                leading_comments = comments;
+                start = Some(name);
+                input = rest;
+            }
+            Err(nom::Err::Failure(e)) => {
+                errors.push(ParseError::from_nom(s, nom::Err::Failure(e)));
+                input = parsers::resynchronize(input);
+            }
+            Err(_) => {}
+        }
+
+        while let Ok((rest, trailing)) = parsers::comments(input) {
+            if rest.is_empty() {
+                if let Some(last) = productions.last_mut() {
+                    for comment in trailing {
+                        last.add_comment(comment);
+                    }
+                }
+                break;
+            }
+            match parsers::production(input) {
+                Ok((rest, mut production)) => {
+                    production.locate(&lines);
+                    productions.push(production);
+                    input = rest;
+                }
+                Err(e) => {
+                    errors.push(ParseError::from_nom(s, e));
+                    input = parsers::resynchronize(input);
+                }
+            }
+        }
+
+        if let Some(first) = productions.first_mut() {
+            first.prepend_comments(leading_comments);
+        }
+        let mut grammar = Grammar::from_parts(productions);
+        grammar.set_start_symbol(start);
+        (grammar, errors)
+    }
+
+    /// Construct a `Grammar` from BNF text, resolving nonterminals defined by
+    /// more than one production according to `policy`
+    ///
+    /// With `DuplicatePolicy::Error`, the error points at the repeated
+    /// definition.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::{DuplicatePolicy, Grammar};
+    ///
+    /// fn main() {
+    ///     let input = "<a> ::= \"x\" <b>
+    ///         <b> ::= \"y\"
+    ///         <a> ::= \"z\"";
+    ///
+    ///     let merged = Grammar::from_str_with(input, DuplicatePolicy::Merge).unwrap();
+    ///     assert_eq!(merged.to_string(), "<a> ::= \"x\" <b> | \"z\"\n<b> ::= \"y\"\n");
+    ///
+    ///     let error = Grammar::from_str_with(input, DuplicatePolicy::Error).unwrap_err();
+    ///     assert!(error.to_string().starts_with("3:9: <a> is already defined at 1:1"));
+    /// }
+    /// ```
+    pub fn from_str_with(s: &str, policy: DuplicatePolicy) -> Result<Grammar, Error> {
+        let grammar: Grammar = s.parse()?;
+        if policy == DuplicatePolicy::Error {
+            if let Some((first, repeated)) = grammar.first_duplicate() {
+                if let (Some(first_span), Some(repeated_span)) =
+                    (first.lhs_span(), repeated.lhs_span())
+                {
+                    return Err(Error::from(ParseError::new(
+                        s,
+                        repeated_span.start.offset,
+                        format!("{} is already defined at {}", first.lhs, first_span.start),
+                    )));
+                }
+            }
+        }
+        grammar.merge_productions(policy)
+    }
+
+    /// Construct a `Grammar` from ISO/IEC 14977 EBNF text.
+    ///
+    /// Options (`[ ]`), repetitions (`{ }`) and groups of alternatives (`( )`)
+    /// are replaced by fresh nonterminals named after the rule containing
+    /// them, e.g. `<number-rep>`. Exceptions (`-`) and special sequences
+    /// (`? ?`) have no BNF equivalent and are rejected.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input = "
+    ///         (* DNA, in EBNF *)
+    ///         dna = base, { base };
+    ///         base = 'A' | 'C' | 'G' | 'T';";
+    ///     let grammar = Grammar::from_ebnf(input).unwrap();
+    ///     let expected: Grammar = "
+    ///         <dna> ::= <base> <dna-rep>
+    ///         <dna-rep> ::= \"\" | <base> <dna-rep>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(grammar, expected);
+    /// }
+    /// ```
+    pub fn from_ebnf(s: &str) -> Result<Grammar, Error> {
+        ebnf::parse(s)
+    }
+
+    /// Construct a `Grammar` from RFC 5234 ABNF text.
+    ///
+    /// Repetitions, options, groups of alternatives and value ranges are
+    /// replaced by fresh nonterminals named after the rule containing them,
+    /// and case-insensitive strings are spelled out letter by letter, e.g.
+    /// `"ok"` becomes `<o-or-O> <k-or-K>`. Core rules such as `DIGIT` or
+    /// `CRLF` which are referenced but not defined are imported from
+    /// `Grammar::abnf_core_rules`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input = "
+    /// status-line = version SP 3DIGIT SP reason CRLF
+    /// version     = %s\"HTTP/1.\" (\"0\" / \"1\")
+    /// reason      = *(VCHAR / SP) ; any printable text
+    /// ";
+    ///     let grammar = Grammar::from_abnf(input).unwrap();
+    ///
+    ///     assert!(grammar.parse_input("HTTP/1.1 200 OK\r\n").is_ok());
+    ///     assert!(grammar.parse_input("http/1.1 200 OK\r\n").is_err());
+    /// }
+    /// ```
+    pub fn from_abnf(s: &str) -> Result<Grammar, Error> {
+        abnf::parse(s)
+    }
+
+    /// Construct the RFC 5234 core rules (`ALPHA`, `DIGIT`, `CRLF`, ...) as a
+    /// `Grammar`
+    pub fn abnf_core_rules() -> Grammar {
+        abnf::core_grammar()
+    }
+
+    /// Print the `Grammar` as RFC 5234 ABNF.
+    ///
+    /// Nonterminal names which are not valid ABNF rule names, or which differ
+    /// from another only by case, are renamed. Terminals are written as
+    /// case-sensitive strings, or as `%x` values where they contain characters
+    /// an ABNF string cannot.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let abnf = grammar.to_abnf();
+    ///
+    ///     assert_eq!(abnf, "dna = base / base dna\r\n\
+    ///                       base = %s\"A\" / %s\"C\" / %s\"G\" / %s\"T\"\r\n");
+    ///     assert_eq!(Grammar::from_abnf(&abnf).unwrap(), grammar);
+    /// }
+    /// ```
+    pub fn to_abnf(&self) -> String {
+        abnf::print(self)
     }
 
     /// Add `Production` to the `Grammar`
     pub fn add_production(&mut self, prod: Production) {
+        if let Some(compiled) = self.compiled.get_mut() {
+            compiled.push(&prod);
+        }
         self.productions.push(prod)
     }
 
     /// Remove `Production` from the `Grammar`
     pub fn remove_production(&mut self, prod: &Production) -> Option<Production> {
         if let Some(pos) = self.productions.iter().position(|x| *x == *prod) {
+            // later productions shift down, so compile again when next needed
+            self.compiled.take();
             Some(self.productions.remove(pos))
         } else {
             None
         }
     }
 
+    /// Build a `Grammar` with one production per nonterminal, in order of
+    /// first definition, resolving repeated definitions according to `policy`
+    ///
+    /// Elsewhere a `Grammar` treats every production of a nonterminal as
+    /// contributing alternatives, as `DuplicatePolicy::Merge` does.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::{DuplicatePolicy, Error, Grammar};
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<a> ::= \"x\"
+    ///         <a> ::= \"y\" | \"x\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     let merged = grammar.merge_productions(DuplicatePolicy::Merge).unwrap();
+    ///     assert_eq!(merged.to_string(), "<a> ::= \"x\" | \"y\"\n");
+    ///
+    ///     let last = grammar.merge_productions(DuplicatePolicy::LastWins).unwrap();
+    ///     assert_eq!(last.to_string(), "<a> ::= \"y\" | \"x\"\n");
+    ///
+    ///     assert_eq!(
+    ///         grammar.merge_productions(DuplicatePolicy::Error),
+    ///         Err(Error::DuplicateProduction(String::from("a")))
+    ///     );
+    /// }
+    /// ```
+    pub fn merge_productions(&self, policy: DuplicatePolicy) -> Result<Grammar, Error> {
+        let mut productions: Vec<Production> = vec![];
+        for production in self.productions_iter() {
+            let existing = productions
+                .iter_mut()
+                .find(|merged| merged.lhs == production.lhs);
+            let existing = match existing {
+                Some(existing) => existing,
+                None => {
+                    productions.push(production.clone());
+                    continue;
+                }
+            };
+
+            match policy {
+                DuplicatePolicy::Merge => {
+                    for expression in production.rhs_iter() {
+                        if !existing.rhs_iter().any(|known| known == expression) {
+                            existing.add_to_rhs(expression.clone());
+                        }
+                    }
+                    for comment in production.comments() {
+                        existing.add_comment(comment.clone());
+                    }
+                }
+                DuplicatePolicy::Error => {
+                    return Err(Error::DuplicateProduction(match production.lhs {
+                        Term::Nonterminal(ref nt) | Term::Terminal(ref nt) => nt.clone(),
+                    }));
+                }
+                DuplicatePolicy::LastWins => *existing = production.clone(),
+            }
+        }
+
+        let mut grammar = Grammar::from_parts(productions);
+        grammar.set_start_symbol(self.start.clone());
+        Ok(grammar)
+    }
+
+    /// The `Grammar` with its terms interned, compiled on first use
+    pub(crate) fn compiled(&self) -> &CompiledGrammar {
+        self.compiled.get_or_init(|| CompiledGrammar::new(self))
+    }
+
+    /// Intern every `Term` of the `Grammar` as a `Symbol`, for cheap cloning
+    /// and comparison
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<a> ::= \"x\" <a> | \"x\"".parse().unwrap();
+    ///     let compiled = grammar.compile();
+    ///
+    ///     assert_eq!(compiled.symbols().len(), 2);
+    ///     assert_eq!(compiled.to_grammar(), grammar);
+    /// }
+    /// ```
+    pub fn compile(&self) -> CompiledGrammar {
+        self.compiled().clone()
+    }
+
+    /// The first production defining a nonterminal, and the next one to
+    /// define it again
+    fn first_duplicate(&self) -> Option<(&Production, &Production)> {
+        self.productions
+            .iter()
+            .enumerate()
+            .find_map(|(i, repeated)| {
+                self.productions[..i]
+                    .iter()
+                    .find(|first| first.lhs == repeated.lhs)
+                    .map(|first| (first, repeated))
+            })
+    }
+
     /// Get iterator of the `Grammar`'s `Production`s
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     pub fn productions_iter(&self) -> Iter {
         Iter {
             iterator: self.productions.iter(),
@@ -50,73 +425,689 @@ impl Grammar {
     }
 
     /// Get mutable iterator of the `Grammar`'s `Production`s
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     pub fn productions_iter_mut(&mut self) -> IterMut {
+        // any term may be rewritten, so compile again when next needed
+        self.compiled.take();
         IterMut {
             iterator: self.productions.iter_mut(),
         }
     }
 
-    fn eval_terminal(&self, term: &Term, rng: &mut StdRng) -> Result<String, Error> {
-        match *term {
-            Term::Nonterminal(ref nt) => self.traverse(&nt, rng),
-            Term::Terminal(ref t) => Ok(t.clone()),
-        }
+    /// The nonterminal sentences are derived from: the declared start
+    /// symbol if there is one, or else the lhs of the first production
+    ///
+    /// In text, the start symbol is declared by a `%start <name>` line ahead
+    /// of the first production. A grammar of just that line derives nothing.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let mut grammar: Grammar = "%start <base>
+    ///         <dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(grammar.start_symbol(), Some("base"));
+    ///     assert!(grammar.parse_input("G").is_ok());
+    ///
+    ///     grammar.set_start_symbol(None);
+    ///     assert_eq!(grammar.start_symbol(), Some("dna"));
+    ///     assert!(grammar.parse_input("GATTACA").is_ok());
+    /// }
+    /// ```
+    pub fn start_symbol(&self) -> Option<&str> {
+        match self.start {
+            Some(ref start) => Some(start),
+            None => match self.productions.first().map(|production| &production.lhs) {
+                Some(Term::Nonterminal(nt)) => Some(nt),
+                _ => None,
+            },
+        }
+    }
+
+    /// Declare the start nonterminal, or with `None` go back to the lhs of the
+    /// first production
+    pub fn set_start_symbol(&mut self, start: Option<String>) {
+        self.start = start;
+        self.compiled.take();
+    }
+
+    /// The declared start nonterminal, if any
+    pub(crate) fn declared_start(&self) -> Option<&str> {
+        self.start.as_deref()
+    }
+
+    /// Check the `Grammar` for nonterminals which are used but never defined,
+    /// productions which cannot be reached from the start symbol, and
+    /// productions with a terminal left hand side
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+    ///         <rna> ::= \"U\""
+    ///         .parse()
+    ///         .unwrap();
+    ///     let report = grammar.validate();
+    ///
+    ///     assert!(!report.is_valid());
+    ///     assert_eq!(report.undefined, vec![String::from("base")]);
+    ///     assert_eq!(report.unreachable, vec![String::from("rna")]);
+    /// }
+    /// ```
+    pub fn validate(&self) -> ValidationReport {
+        validate::validate(self)
+    }
+
+    /// Compute which nonterminals can derive a string of terminals, the
+    /// minimum depth of those derivations, and nullable, FIRST and FOLLOW sets
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"
+    ///         <loop> ::= <loop> <base>"
+    ///         .parse()
+    ///         .unwrap();
+    ///     let analysis = grammar.analysis();
+    ///
+    ///     assert_eq!(analysis.unproductive(), vec!["loop"]);
+    ///     assert_eq!(analysis.min_depth("dna"), Some(2));
+    ///     assert_eq!(analysis.follow("base").unwrap().len(), 5);
+    /// }
+    /// ```
+    pub fn analysis(&self) -> GrammarAnalysis {
+        GrammarAnalysis::new(self)
+    }
+
+    /// Build an LL(1) prediction table from the nullable, FIRST and FOLLOW
+    /// sets of self, reporting every conflict
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<list> ::= \"x\" | \"x\" \",\" <list>"
+    ///         .parse()
+    ///         .unwrap();
+    ///     let table = grammar.ll1_table();
+    ///
+    ///     assert!(!table.is_ll1());
+    ///     for conflict in table.conflicts() {
+    ///         println!("{}", conflict);
+    ///     }
+    /// }
+    /// ```
+    pub fn ll1_table(&self) -> PredictionTable<'_> {
+        PredictionTable::new(self)
+    }
+
+    /// Build LR(0) ACTION and GOTO tables from the item sets of self,
+    /// reducing on every lookahead
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<list> ::= \"x\" \",\" <list> | \"x\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     assert!(!grammar.lr0_table().is_conflict_free());
+    /// }
+    /// ```
+    pub fn lr0_table(&self) -> LrTable<'_> {
+        LrTable::new(self, LrKind::Lr0)
+    }
+
+    /// Build SLR(1) ACTION and GOTO tables from the item sets of self,
+    /// reducing on the FOLLOW set of the reduced nonterminal
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<list> ::= \"x\" \",\" <list> | \"x\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     assert!(grammar.slr1_table().is_conflict_free());
+    /// }
+    /// ```
+    pub fn slr1_table(&self) -> LrTable<'_> {
+        LrTable::new(self, LrKind::Slr1)
+    }
+
+    /// Build LALR(1) ACTION and GOTO tables from the item sets of self,
+    /// reducing on lookaheads propagated through the automaton
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<s> ::= <a> \"x\" | <b> \"x\"
+    ///         <a> ::= \"a\"
+    ///         <b> ::= \"a\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     for conflict in grammar.lalr1_table().conflicts() {
+    ///         println!("{}", conflict);
+    ///     }
+    /// }
+    /// ```
+    pub fn lalr1_table(&self) -> LrTable<'_> {
+        LrTable::new(self, LrKind::Lalr1)
+    }
+
+    /// Build an equivalent `Grammar` without direct or indirect left
+    /// recursion, and a map from each nonterminal it adds to the nonterminal
+    /// it was split from
+    ///
+    /// Each left recursive nonterminal `<a>` gets a fresh tail nonterminal,
+    /// normally `<a-tail>`, matching what followed its left recursion.
+    /// Nonterminals left with no expressions, such as `<a> ::= <a>`, are
+    /// removed along with the expressions using them. Left recursion hidden
+    /// behind nullable nonterminals is left in place.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<expr> ::= <expr> \"+\" <term> | <term>
+    ///         <term> ::= \"x\""
+    ///         .parse()
+    ///         .unwrap();
+    ///     let (grammar, origins) = grammar.eliminate_left_recursion();
+    ///
+    ///     assert_eq!(
+    ///         grammar.to_string(),
+    ///         "<expr> ::= <term> <expr-tail>
+    /// <expr-tail> ::= \"+\" <term> <expr-tail> | \"\"
+    /// <term> ::= \"x\"
+    /// "
+    ///     );
+    ///     assert_eq!(origins["expr-tail"], "expr");
+    /// }
+    /// ```
+    pub fn eliminate_left_recursion(&self) -> (Grammar, BTreeMap<String, String>) {
+        transform::eliminate_left_recursion(self)
+    }
+
+    /// Build an equivalent `Grammar` without `""` alternatives, and report
+    /// whether the start symbol derives the empty string
+    ///
+    /// Each expression using nullable nonterminals is replaced by every way
+    /// of leaving them out. A nullable start nonterminal keeps a `""`
+    /// alternative, and if it appears on a right hand side it is first
+    /// replaced by a fresh start nonterminal, so it is the only one.
+    /// Nonterminals which derived only the empty string are removed along
+    /// with the expressions using them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<address> ::= <street> <opt-apt-num>
+    ///         <street> ::= \"main\"
+    ///         <opt-apt-num> ::= \"1\" | \"\""
+    ///         .parse()
+    ///         .unwrap();
+    ///     let (grammar, nullable_start) = grammar.remove_epsilon_productions();
+    ///
+    ///     assert!(!nullable_start);
+    ///     assert_eq!(
+    ///         grammar.to_string(),
+    ///         "<address> ::= <street> <opt-apt-num> | <street>
+    /// <street> ::= \"main\"
+    /// <opt-apt-num> ::= \"1\"
+    /// "
+    ///     );
+    /// }
+    /// ```
+    pub fn remove_epsilon_productions(&self) -> (Grammar, bool) {
+        normal_form::remove_epsilon_productions(self)
+    }
+
+    /// Build an equivalent `Grammar` without expressions which are a single
+    /// nonterminal, copying in that nonterminal's other expressions instead.
+    /// Nonterminals left with no expressions, such as one whose only
+    /// expression is an undefined nonterminal, are removed along with the
+    /// expressions using them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<a> ::= <b> | \"a\"
+    ///         <b> ::= \"b\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(
+    ///         grammar.remove_unit_productions().to_string(),
+    ///         "<a> ::= \"a\" | \"b\"\n<b> ::= \"b\"\n"
+    ///     );
+    /// }
+    /// ```
+    pub fn remove_unit_productions(&self) -> Grammar {
+        normal_form::remove_unit_productions(self)
+    }
+
+    /// Build an equivalent `Grammar` where no two expressions of a
+    /// nonterminal start with the same term
+    ///
+    /// Expressions sharing a first term are replaced by their longest common
+    /// prefix and a fresh `<name-suffix>` nonterminal for the rest, repeating
+    /// until nothing is shared. Left recursion is not removed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<if> ::= \"if\" <s> | \"if\" <s> \"else\" <s>
+    ///         <s> ::= \"s\""
+    ///         .parse()
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(
+    ///         grammar.left_factor().to_string(),
+    ///         "<if> ::= \"if\" <s> <if-suffix>
+    /// <if-suffix> ::= \"\" | \"else\" <s>
+    /// <s> ::= \"s\"
+    /// "
+    ///     );
+    /// }
+    /// ```
+    pub fn left_factor(&self) -> Grammar {
+        transform::left_factor(self)
+    }
+
+    /// Convert to an equivalent `Grammar` in Chomsky Normal Form, where every
+    /// expression is a single terminal or two nonterminals
+    ///
+    /// If the start symbol derives the empty string it keeps a `""`
+    /// alternative, and is replaced by a fresh start nonterminal if it
+    /// appears on any right hand side. Terminals in longer expressions
+    /// and the links of split up expressions get fresh nonterminals, named
+    /// so they collide with no existing nonterminal. Nonterminals left with
+    /// no expressions are removed along with the expressions using them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<list> ::= \"x\" \",\" <list> | \"x\""
+    ///         .parse()
+    ///         .unwrap();
+    ///     let cnf = grammar.to_cnf();
+    ///
+    ///     assert!(cnf.is_cnf());
+    ///     assert_eq!(
+    ///         cnf.to_string(),
+    ///         "<list> ::= <x> <list-rest> | \"x\"
+    /// <list-rest> ::= <,> <list>
+    /// <x> ::= \"x\"
+    /// <,> ::= \",\"
+    /// "
+    ///     );
+    /// }
+    /// ```
+    pub fn to_cnf(&self) -> Grammar {
+        normal_form::to_cnf(self)
+    }
+
+    /// Whether self is in Chomsky Normal Form: every expression is a single
+    /// terminal or two nonterminals, except that the start symbol may have a
+    /// `""` alternative if no right hand side uses it. Every production needs
+    /// at least one expression.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<s> ::= <s> <s> | \"x\"".parse().unwrap();
+    ///     assert!(grammar.is_cnf());
+    ///
+    ///     let grammar: Grammar = "<s> ::= \"x\" <s> | \"x\"".parse().unwrap();
+    ///     assert!(!grammar.is_cnf());
+    /// }
+    /// ```
+    pub fn is_cnf(&self) -> bool {
+        normal_form::is_cnf(self)
+    }
+
+    /// Convert to an equivalent `Grammar` in Greibach Normal Form, where
+    /// every expression starts with a terminal, and report whether self
+    /// derives the empty string, which a grammar in that form cannot
+    ///
+    /// Nonterminals deriving no string of terminals, including undefined
+    /// ones, are removed along with the expressions using them, though an
+    /// unproductive start symbol stays the declared start. Left
+    /// recursive nonterminals `<a>` get fresh tail nonterminals, normally
+    /// `<a-tail>`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<list> ::= <list> \",\" <item> | <item> | \"\"
+    ///         <item> ::= \"x\""
+    ///         .parse()
+    ///         .unwrap();
+    ///     let (gnf, derives_empty) = grammar.to_gnf();
+    ///
+    ///     assert!(derives_empty);
+    ///     assert_eq!(
+    ///         gnf.to_string(),
+    ///         "<list> ::= \",\" <item> | \",\" <item> <list-tail> | \"x\" | \"x\" <list-tail>
+    /// <list-tail> ::= \",\" <item> | \",\" <item> <list-tail>
+    /// <item> ::= \"x\"
+    /// "
+    ///     );
+    /// }
+    /// ```
+    pub fn to_gnf(&self) -> (Grammar, bool) {
+        normal_form::to_gnf(self)
+    }
+
+    /// The nonterminal generation and parsing begin from, the declared start
+    /// symbol or else the lhs of the first production. Failures are reported
+    /// with `error`, so each caller keeps its own `Error` variant.
+    pub(crate) fn start_rule(&self, error: fn(String) -> Error) -> Result<&str, Error> {
+        if let Some(start) = self.declared_start() {
+            return Ok(start);
+        }
+        match self.productions_iter().next() {
+            Some(production) => match production.lhs {
+                Term::Nonterminal(ref nt) => Ok(nt),
+                Term::Terminal(_) => Err(error(format!(
+                    "Terminal type cannot define a production in '{}'!",
+                    production
+                ))),
+            },
+            None => Err(error(String::from("Failed to get first production!"))),
+        }
+    }
+
+    /// Generate a random sentence from self and seed for random.
+    /// Use if interested in reproducing the output generated.
+    /// Begins from the start symbol, see `start_symbol`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// extern crate rand;
+    /// use rand::{SeedableRng, rngs::StdRng};
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let seed: [u8; 32] = [0; 32];
+    ///     let mut rng: StdRng = SeedableRng::from_seed(seed);
+    ///     let sentence = grammar.generate_seeded(&mut rng);
+    ///     # let sentence_clone = sentence.clone();
+    ///     match sentence {
+    ///         Ok(s) => println!("random sentence: {}", s),
+    ///         Err(e) => println!("something went wrong: {}!", e)
+    ///     }
+    ///
+    ///     # assert!(sentence_clone.is_ok());
+    /// }
+    /// ```
+    pub fn generate_seeded(&self, rng: &mut StdRng) -> Result<String, Error> {
+        GenerateOptions::new().generate(self, rng)
+    }
+
+    /// Generate a random sentence derived from the nonterminal named `start`,
+    /// using `rng` for random choices.
+    ///
+    /// It is an error if `start` has no production.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// extern crate rand;
+    /// use rand::{SeedableRng, rngs::StdRng};
+    /// use bnf::{Error, Grammar};
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+    ///
+    ///     let base = grammar.generate_seeded_from(&mut rng, "base").unwrap();
+    ///     assert_eq!(base.len(), 1);
+    ///     assert_eq!(
+    ///         grammar.generate_seeded_from(&mut rng, "rna"),
+    ///         Err(Error::UndefinedNonterminal(String::from("rna")))
+    ///     );
+    /// }
+    /// ```
+    pub fn generate_seeded_from(&self, rng: &mut StdRng, start: &str) -> Result<String, Error> {
+        GenerateOptions::new().start(start).generate(self, rng)
+    }
+
+    /// Generate a random sentence derived from the nonterminal named `start`,
+    /// as `generate_seeded_from` does.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let base = grammar.generate_from("base").unwrap();
+    ///
+    ///     assert!(["A", "C", "G", "T"].contains(&base.as_str()));
+    ///     assert!(grammar.generate_from("rna").is_err());
+    /// }
+    /// ```
+    pub fn generate_from(&self, start: &str) -> Result<String, Error> {
+        let mut seed: [u8; 32] = [0; 32];
+        thread_rng().fill(&mut seed);
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        self.generate_seeded_from(&mut rng, start)
+    }
+
+    /// Generate a random sentence from self.
+    /// Begins from the start symbol, see `start_symbol`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let sentence = grammar.generate();
+    ///     # let sentence_clone = sentence.clone();
+    ///     match sentence {
+    ///         Ok(s) => println!("random sentence: {}", s),
+    ///         Err(e) => println!("something went wrong: {}!", e)
+    ///     }
+    ///
+    ///     # assert!(sentence_clone.is_ok());
+    /// }
+    /// ```
+    pub fn generate(&self) -> Result<String, Error> {
+        // let seed: &[_] = &[1, 2, 3, 4];
+        let mut seed: [u8; 32] = [0; 32];
+        thread_rng().fill(&mut seed);
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        self.generate_seeded(&mut rng)
+    }
+
+    /// Generate a random sentence from self and seed for random, expanding
+    /// no more than `max_depth` levels of productions.
+    /// Begins from the start symbol, see `start_symbol`.
+    ///
+    /// Every expansion picks only among alternatives whose shortest
+    /// derivation fits in the depth left, so recursive grammars always
+    /// produce a sentence, and alternatives which can never finish are never
+    /// picked. It is an error when even the shortest derivation of the start
+    /// nonterminal is deeper than `max_depth`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// extern crate rand;
+    /// use rand::{SeedableRng, rngs::StdRng};
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+    ///     let sentence = grammar.generate_seeded_bounded(&mut rng, 5).unwrap();
+    ///
+    ///     assert!(!sentence.is_empty() && sentence.len() <= 4);
+    /// }
+    /// ```
+    pub fn generate_seeded_bounded(
+        &self,
+        rng: &mut StdRng,
+        max_depth: usize,
+    ) -> Result<String, Error> {
+        GenerateOptions::new()
+            .max_depth(max_depth)
+            .generate(self, rng)
+    }
+
+    /// Generate a random sentence from self, expanding no more than
+    /// `max_depth` levels of productions, as in `generate_seeded_bounded`.
+    /// Begins from the start symbol, see `start_symbol`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input =
+    ///         "<dna> ::= <base> | <base> <dna>
+    ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let sentence = grammar.generate_bounded(10);
+    ///
+    ///     assert!(sentence.is_ok());
+    /// }
+    /// ```
+    pub fn generate_bounded(&self, max_depth: usize) -> Result<String, Error> {
+        let mut seed: [u8; 32] = [0; 32];
+        thread_rng().fill(&mut seed);
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        self.generate_seeded_bounded(&mut rng, max_depth)
     }
 
-    fn traverse(&self, ident: &str, rng: &mut StdRng) -> Result<String, Error> {
-        const STACK_RED_ZONE: usize = 32 * 1024; // 32KB
-                                                 // heavy recursion happening, we've hit out tolerable threshold
-        if let Some(remaining) = stacker::remaining_stack() {
-            if remaining < STACK_RED_ZONE {
-                return Err(Error::RecursionLimit(format!(
-                    "Limit for recursion reached processing <{}>!",
-                    ident
-                )));
-            }
-        }
-
-        let nonterm = Term::Nonterminal(ident.to_string());
-        let production;
-        let find_lhs = self.productions_iter().find(|&x| x.lhs == nonterm);
-
-        match find_lhs {
-            Some(p) => production = p,
-            None => return Ok(nonterm.to_string()),
-        }
-
-        let expression;
-        let expressions = production.rhs_iter().collect::<Vec<&Expression>>();
-
-        match expressions.choose(rng) {
-            Some(e) => expression = e,
-            None => {
-                return Err(Error::GenerateError(String::from(
-                    "Couldn't select random Expression!",
-                )));
-            }
-        }
-
-        let mut result = String::new();
-        for term in expression.terms_iter() {
-            match self.eval_terminal(&term, rng) {
-                Ok(s) => result = result + &s,
-                Err(e) => return Err(e),
-            }
-        }
-
-        Ok(result)
+    /// Generate a random sentence from self as configured by `options`,
+    /// using any `rng` for random choices. The other generate functions are
+    /// shorthands for particular `GenerateOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// extern crate rand;
+    /// use bnf::{Error, GenerateOptions, Grammar, UndefinedPolicy};
+    ///
+    /// fn main() {
+    ///     let input = "<greeting> ::= \"hello \" <name>";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let options = GenerateOptions::new().undefined(UndefinedPolicy::Error);
+    ///
+    ///     assert_eq!(
+    ///         grammar.generate_with(&mut rand::thread_rng(), &options),
+    ///         Err(Error::UndefinedNonterminal(String::from("name")))
+    ///     );
+    /// }
+    /// ```
+    pub fn generate_with<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        options: &GenerateOptions,
+    ) -> Result<String, Error> {
+        options.generate(self, rng)
     }
 
-    /// Generate a random sentence from self and seed for random.
-    /// Use if interested in reproducing the output generated.
-    /// Begins from lhs of first production.
+    /// Parse `input` as a sentence of the language defined by self.
+    /// Begins from the start symbol, see `start_symbol`.
+    ///
+    /// Any context-free grammar is supported, including left recursive and
+    /// ambiguous ones. When `input` has several derivations, one is returned.
     ///
     /// # Example
     ///
     /// ```rust
     /// extern crate bnf;
-    /// extern crate rand;
-    /// use rand::{SeedableRng, rngs::StdRng};
     /// use bnf::Grammar;
     ///
     /// fn main() {
@@ -124,43 +1115,23 @@ impl Grammar {
     ///         "<dna> ::= <base> | <base> <dna>
     ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
     ///     let grammar: Grammar = input.parse().unwrap();
-    ///     let seed: [u8; 32] = [0; 32];
-    ///     let mut rng: StdRng = SeedableRng::from_seed(seed);
-    ///     let sentence = grammar.generate_seeded(&mut rng);
-    ///     # let sentence_clone = sentence.clone();
-    ///     match sentence {
-    ///         Ok(s) => println!("random sentence: {}", s),
-    ///         Err(e) => println!("something went wrong: {}!", e)
+    ///     let parse_tree = grammar.parse_input("GATTACA");
+    ///     # let parse_tree_clone = parse_tree.clone();
+    ///     match parse_tree {
+    ///         Ok(tree) => println!("parse tree:\n{}", tree),
+    ///         Err(e) => println!("not a DNA sequence: {}!", e)
     ///     }
     ///
-    ///     # assert!(sentence_clone.is_ok());
+    ///     # assert!(parse_tree_clone.is_ok());
+    ///     # assert!(grammar.parse_input("GATTACO").is_err());
     /// }
     /// ```
-    pub fn generate_seeded(&self, rng: &mut StdRng) -> Result<String, Error> {
-        let start_rule: String;
-        let first_production = self.productions_iter().next();
-
-        match first_production {
-            Some(term) => match term.lhs {
-                Term::Nonterminal(ref nt) => start_rule = nt.clone(),
-                Term::Terminal(_) => {
-                    return Err(Error::GenerateError(format!(
-                        "Termainal type cannot define a production in '{}'!",
-                        term
-                    )));
-                }
-            },
-            None => {
-                return Err(Error::GenerateError(String::from(
-                    "Failed to get first production!",
-                )));
-            }
-        }
-        self.traverse(&start_rule, rng)
+    pub fn parse_input<'gram>(&'gram self, input: &str) -> Result<ParseTree<'gram>, Error> {
+        let start = self.start_rule(Error::ParseInputError)?;
+        self.parse_input_from(start, input)
     }
 
-    /// Generate a random sentence from self.
-    /// Begins from lhs of first production.
+    /// Parse `input` as a sentence derived from the nonterminal named `start`.
     ///
     /// # Example
     ///
@@ -173,27 +1144,96 @@ impl Grammar {
     ///         "<dna> ::= <base> | <base> <dna>
     ///         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
     ///     let grammar: Grammar = input.parse().unwrap();
-    ///     let sentence = grammar.generate();
-    ///     # let sentence_clone = sentence.clone();
-    ///     match sentence {
-    ///         Ok(s) => println!("random sentence: {}", s),
-    ///         Err(e) => println!("something went wrong: {}!", e)
-    ///     }
     ///
-    ///     # assert!(sentence_clone.is_ok());
+    ///     assert!(grammar.parse_input_from("base", "G").is_ok());
+    ///     assert!(grammar.parse_input_from("base", "GA").is_err());
     /// }
     /// ```
-    pub fn generate(&self) -> Result<String, Error> {
-        // let seed: &[_] = &[1, 2, 3, 4];
-        let mut seed: [u8; 32] = [0; 32];
-        thread_rng().fill(&mut seed);
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
-        self.generate_seeded(&mut rng)
+    pub fn parse_input_from<'gram>(
+        &'gram self,
+        start: &str,
+        input: &str,
+    ) -> Result<ParseTree<'gram>, Error> {
+        earley::parse(self, start, input)
+    }
+
+    /// Parse `input` as a sentence of the language defined by self, keeping
+    /// every derivation.
+    /// Begins from the start symbol, see `start_symbol`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let input = "<expr> ::= <expr> \"-\" <expr> | \"1\"";
+    ///     let grammar: Grammar = input.parse().unwrap();
+    ///     let forest = grammar.parse_forest("1-1-1").unwrap();
+    ///
+    ///     // (1-1)-1 and 1-(1-1)
+    ///     assert_eq!(forest.count(), Some(2));
+    ///     for tree in forest.trees() {
+    ///         println!("{}", tree);
+    ///     }
+    /// }
+    /// ```
+    pub fn parse_forest<'gram>(&'gram self, input: &str) -> Result<ParseForest<'gram>, Error> {
+        let start = self.start_rule(Error::ParseInputError)?;
+        self.parse_forest_from(start, input)
+    }
+
+    /// Parse `input` as a sentence derived from the nonterminal named `start`,
+    /// keeping every derivation.
+    pub fn parse_forest_from<'gram>(
+        &'gram self,
+        start: &str,
+        input: &str,
+    ) -> Result<ParseForest<'gram>, Error> {
+        earley::parse_forest(self, start, input)
+    }
+}
+
+impl Clone for Grammar {
+    fn clone(&self) -> Grammar {
+        Grammar {
+            productions: self.productions.clone(),
+            start: self.start.clone(),
+            compiled: OnceLock::new(),
+        }
+    }
+}
+
+impl PartialEq for Grammar {
+    fn eq(&self, other: &Grammar) -> bool {
+        self.start == other.start && self.productions == other.productions
+    }
+}
+
+impl Eq for Grammar {}
+
+impl Hash for Grammar {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.start.hash(state);
+        self.productions.hash(state);
+    }
+}
+
+impl fmt::Debug for Grammar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Grammar")
+            .field("start", &self.start)
+            .field("productions", &self.productions)
+            .finish()
     }
 }
 
 impl fmt::Display for Grammar {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref start) = self.start {
+            writeln!(f, "%start {}", Term::Nonterminal(start.clone()))?;
+        }
         writeln!(
             f,
             "{}",
@@ -212,7 +1252,7 @@ impl str::FromStr for Grammar {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match parsers::grammar_complete(s) {
             Result::Ok((_, o)) => Ok(o),
-            Result::Err(e) => Err(Error::from(e)),
+            Result::Err(e) => Err(Error::from(ParseError::from_nom(s, e))),
         }
     }
 }
@@ -259,7 +1299,7 @@ mod tests {
             if productions.is_empty() {
                 productions.push(Production::arbitrary(g));
             }
-            Grammar { productions }
+            Grammar::from_parts(productions)
         }
     }
 
@@ -397,6 +1437,83 @@ mod tests {
         assert!(grammar.is_err(), "{:?} should be error", grammar);
     }
 
+    #[test]
+    fn parse_error_messages() {
+        let cases = [
+            (
+                "<a> ::= \"x\"\n<b> ::= <a",
+                (2, 11),
+                "expected `>` to close nonterminal started at 2:9",
+            ),
+            ("<a> ::= \"x\" |", (1, 14), "expected a term after `|`"),
+            ("<a> \"x\"", (1, 5), "expected `::=`"),
+            ("<a> ::= \"\\q\"", (1, 10), "invalid escape sequence"),
+            (
+                "<a> ::= \"x\"\n  <b> ::= \"y\" )",
+                (2, 15),
+                "expected a term, `|`, `;` or the next production",
+            ),
+        ];
+
+        for &(source, (line, column), expected) in &cases {
+            match source.parse::<Grammar>() {
+                Err(Error::ParseError(ref e)) => {
+                    assert_eq!((e.position.line, e.position.column), (line, column));
+                    assert_eq!(e.expected, expected);
+                }
+                e => panic!("{} should be Error::ParseError: {:?}", source, e),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_error_display() {
+        let error = "<a> ::= \"x\"\n<b> ::= <a".parse::<Grammar>().unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "2:11: expected `>` to close nonterminal started at 2:9
+  |
+2 | <b> ::= <a
+  |           ^"
+        );
+    }
+
+    #[test]
+    fn recovers_after_errors() {
+        let input = "<a> ::= <b> | <c>
+            <b> ::= \"x\" | ;
+            <c> ::= \"\\q\" \";\" ; <d> ::= \"z\"
+            <e> ::= \"w\" \"unclosed";
+        let (grammar, errors) = Grammar::from_str_recovering(input);
+
+        let expected: Grammar = "<a> ::= <b> | <c>
+            <d> ::= \"z\""
+            .parse()
+            .unwrap();
+        assert_eq!(grammar, expected);
+
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|e| (e.position.line, e.position.column))
+            .collect();
+        assert_eq!(positions, vec![(2, 26), (3, 22), (4, 34)]);
+
+        let d = grammar.productions_iter().nth(1).unwrap();
+        assert_eq!(d.span().unwrap().start.line, 3);
+    }
+
+    #[test]
+    fn recovering_matches_from_str() {
+        let input = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+        let (grammar, errors) = Grammar::from_str_recovering(input);
+
+        assert!(errors.is_empty());
+        assert_eq!(grammar, input.parse().unwrap());
+        assert_eq!(Grammar::from_str_recovering("  # nothing\n").1, vec![]);
+    }
+
     #[test]
     fn parse_error_on_incomplete() {
         let result: Result<Grammar, _> = "".parse();
@@ -411,18 +1528,399 @@ mod tests {
     }
 
     #[test]
-    fn recursion_limit() {
+    fn self_recursion_is_unproductive() {
         let grammar: Result<Grammar, _> = "<nonterm> ::= <nonterm>".parse();
         assert!(grammar.is_ok(), "{:?} should be ok", grammar);
         let sentence = grammar.unwrap().generate();
         assert!(sentence.is_err(), "{:?} should be err", sentence);
         match sentence {
             Err(e) => match e {
-                Error::RecursionLimit(_) => (),
-                e => panic!("should should be Error::RecursionLimit: {:?}", e),
+                Error::Unproductive(ref nts) => assert_eq!(nts, &vec![String::from("nonterm")]),
+                e => panic!("should should be Error::Unproductive: {:?}", e),
             },
-            Ok(s) => panic!("should should be Error::RecursionLimit: {}", s),
+            Ok(s) => panic!("should should be Error::Unproductive: {}", s),
+        }
+    }
+
+    #[test]
+    fn unproductive_names_reachable_nonterminals() {
+        let grammar: Grammar = "<start> ::= \"x\" | <a>
+            <a> ::= <b> | \"y\" <c>
+            <b> ::= <a> <b>
+            <c> ::= <c> \"z\"
+            <unused> ::= <unused>"
+            .parse()
+            .unwrap();
+        let sentence = grammar.generate();
+        assert_eq!(
+            sentence,
+            Err(Error::Unproductive(vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c")
+            ]))
+        );
+    }
+
+    #[test]
+    fn bounded_generation_terminates() {
+        let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([7; 32]);
+
+        for max_depth in 2..12 {
+            for _ in 0..50 {
+                let sentence = grammar.generate_seeded_bounded(&mut rng, max_depth);
+                let sentence = sentence.unwrap();
+                assert!(!sentence.is_empty() && sentence.len() < max_depth);
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_generation_avoids_unproductive() {
+        let grammar: Grammar = "<start> ::= <loop> | <a> <start> | <a>
+            <a> ::= \"a\" | <loop> \"b\"
+            <loop> ::= <loop> \"c\"
+            <other> ::= <not-defined>"
+            .parse()
+            .unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([3; 32]);
+
+        for _ in 0..100 {
+            let sentence = grammar.generate_seeded_bounded(&mut rng, 8).unwrap();
+            assert!(sentence.chars().all(|c| c == 'a'), "{}", sentence);
+        }
+    }
+
+    #[test]
+    fn empty_grammar_has_no_start() {
+        let grammar = Grammar::new();
+        let missing = String::from("Failed to get first production!");
+
+        assert_eq!(
+            grammar.generate(),
+            Err(Error::GenerateError(missing.clone()))
+        );
+        assert_eq!(
+            grammar.parse_input("x").map(|_| ()),
+            Err(Error::ParseInputError(missing.clone()))
+        );
+        assert_eq!(
+            grammar.parse_forest("x").map(|_| ()),
+            Err(Error::ParseInputError(missing))
+        );
+    }
+
+    #[test]
+    fn bounded_generation_too_shallow() {
+        let grammar: Grammar = "<a> ::= <b> <b>
+            <b> ::= <c>
+            <c> ::= \"c\""
+            .parse()
+            .unwrap();
+
+        match grammar.generate_bounded(2) {
+            Err(Error::GenerateError(ref e)) => assert_eq!(
+                e,
+                "<a> needs a depth of at least 3 to generate, more than the maximum of 2!"
+            ),
+            e => panic!("should be Error::GenerateError: {:?}", e),
+        }
+        assert_eq!(grammar.generate_bounded(3), Ok(String::from("cc")));
+
+        let unproductive: Grammar = "<loop> ::= <loop>".parse().unwrap();
+        assert_eq!(
+            unproductive.generate_bounded(100),
+            Err(Error::Unproductive(vec![String::from("loop")]))
+        );
+    }
+
+    #[test]
+    fn generation_uses_every_definition() {
+        let grammar: Grammar = "<a> ::= \"x\"
+            <a> ::= \"y\""
+            .parse()
+            .unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let sentences: Vec<String> = (0..50)
+            .map(|_| grammar.generate_seeded(&mut rng).unwrap())
+            .collect();
+
+        assert!(sentences.iter().any(|s| s == "x"));
+        assert!(sentences.iter().any(|s| s == "y"));
+        assert!(grammar.analysis().is_productive("a"));
+    }
+
+    #[test]
+    fn comments_round_trip() {
+        let input = "# leading
+            <a> ::= \"x\" # between
+                | <b> /* inline */ | \"y\" # trailing
+            <b> ::= \"b\" ; # after semicolon
+            # last";
+        let mut grammar: Grammar = input.parse().unwrap();
+        let comments = |grammar: &Grammar| -> Vec<Vec<String>> {
+            grammar
+                .productions_iter()
+                .map(|production| production.comments().to_vec())
+                .collect()
+        };
+        assert_eq!(
+            comments(&grammar),
+            vec![
+                vec![" leading", " between", " inline ", " trailing"],
+                vec![" after semicolon", " last"],
+            ]
+        );
+
+        grammar
+            .productions_iter_mut()
+            .next()
+            .unwrap()
+            .add_comment(String::from(" ends */ early\n"));
+        let reparsed: Grammar = grammar.to_string().parse().unwrap();
+        assert_eq!(reparsed, grammar);
+        assert_eq!(reparsed.to_string(), grammar.to_string());
+        assert_eq!(
+            comments(&reparsed)[0],
+            vec![
+                " leading",
+                " between",
+                " inline ",
+                " trailing",
+                " ends */ early",
+                ""
+            ]
+        );
+        assert_eq!(comments(&reparsed)[1], comments(&grammar)[1]);
+    }
+
+    #[test]
+    fn merge_productions_policies() {
+        let input = "%start <b>
+            # first
+            <a> ::= \"x\" | <b>
+            <b> ::= \"b\"
+            # second
+            <a> ::= \"y\" | \"x\"";
+        let grammar: Grammar = input.parse().unwrap();
+
+        let merged = grammar.merge_productions(DuplicatePolicy::Merge).unwrap();
+        assert_eq!(
+            merged.to_string(),
+            "%start <b>\n# first\n# second\n<a> ::= \"x\" | <b> | \"y\"\n<b> ::= \"b\"\n"
+        );
+        assert_eq!(
+            merged.merge_productions(DuplicatePolicy::Error),
+            Ok(merged.clone())
+        );
+
+        let last = grammar
+            .merge_productions(DuplicatePolicy::LastWins)
+            .unwrap();
+        assert_eq!(
+            last.to_string(),
+            "%start <b>\n# second\n<a> ::= \"y\" | \"x\"\n<b> ::= \"b\"\n"
+        );
+
+        assert_eq!(
+            grammar.merge_productions(DuplicatePolicy::Error),
+            Err(Error::DuplicateProduction(String::from("a")))
+        );
+        match Grammar::from_str_with(input, DuplicatePolicy::Error) {
+            Err(Error::ParseError(e)) => {
+                assert_eq!((e.position.line, e.position.column), (6, 13));
+                assert_eq!(e.expected, "<a> is already defined at 3:13");
+            }
+            e => panic!("should be Error::ParseError: {:?}", e),
+        }
+        assert_eq!(merged.start_symbol(), Some("b"));
+        assert_eq!(
+            Grammar::from_str_with(input, DuplicatePolicy::default()),
+            Ok(merged)
+        );
+        assert_eq!(last.start_symbol(), Some("b"));
+        assert_eq!(
+            Grammar::from_str_with(input, DuplicatePolicy::LastWins),
+            Ok(last)
+        );
+    }
+
+    #[test]
+    fn declared_start_symbol() {
+        let input = "# addresses
+            %start <street>
+            # the whole address
+            <address> ::= <street> \",\" <town>
+            <street> ::= \"main\" | \"high\"
+            <town> ::= \"springfield\"";
+        let grammar: Grammar = input.parse().unwrap();
+
+        assert_eq!(grammar.start_symbol(), Some("street"));
+        assert_eq!(
+            grammar.to_string(),
+            "%start <street>
+# addresses
+# the whole address
+<address> ::= <street> \",\" <town>
+<street> ::= \"main\" | \"high\"
+<town> ::= \"springfield\"
+"
+        );
+        assert_eq!(grammar.to_string().parse::<Grammar>(), Ok(grammar.clone()));
+        assert_eq!(
+            Grammar::from_str_recovering(input),
+            (grammar.clone(), vec![])
+        );
+
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        for _ in 0..10 {
+            let street = grammar.generate_seeded(&mut rng).unwrap();
+            assert!(street == "main" || street == "high", "{}", street);
+        }
+        assert!(grammar.parse_input("main").is_ok());
+        assert!(grammar.parse_input("main,springfield").is_err());
+        assert_eq!(
+            grammar.validate().unreachable,
+            vec![String::from("address"), String::from("town")]
+        );
+        assert_eq!(
+            grammar
+                .analysis()
+                .follow("street")
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["", ","]
+        );
+
+        let mut undeclared = grammar.clone();
+        undeclared.set_start_symbol(None);
+        assert_ne!(undeclared, grammar);
+        assert_eq!(undeclared.start_symbol(), Some("address"));
+        assert!(undeclared.parse_input("main,springfield").is_ok());
+    }
+
+    #[test]
+    fn start_directive_errors() {
+        match "%start street\n<street> ::= \"main\"".parse::<Grammar>() {
+            Err(Error::ParseError(e)) => {
+                assert_eq!((e.position.line, e.position.column), (1, 8));
+                assert_eq!(e.expected, "expected a nonterminal after `%start`");
+            }
+            e => panic!("should be Error::ParseError: {:?}", e),
+        }
+
+        let (grammar, errors) =
+            Grammar::from_str_recovering("%start street\n<street> ::= \"main\"");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(grammar.productions_iter().count(), 1);
+        assert_eq!(grammar.start_symbol(), Some("street"));
+
+        let grammar: Grammar = "%start <road>\n<street> ::= \"main\"".parse().unwrap();
+        assert_eq!(
+            grammar.generate(),
+            Err(Error::UndefinedNonterminal(String::from("road")))
+        );
+    }
+
+    #[test]
+    fn start_directive_alone() {
+        let grammar: Grammar = "%start <s>".parse().unwrap();
+        assert_eq!(grammar.productions_iter().count(), 0);
+        assert_eq!(grammar.start_symbol(), Some("s"));
+        assert!(grammar.parse_input("").is_err());
+        assert_eq!(grammar.to_string().parse::<Grammar>(), Ok(grammar));
+
+        match "%start <s>\n<s> ::=".parse::<Grammar>() {
+            Err(Error::ParseError(e)) => assert_eq!(e.position.line, 2),
+            e => panic!("should be Error::ParseError: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn generate_from_nonterminal() {
+        let grammar: Grammar = "<address> ::= <street> \",\" <town>
+            <street> ::= \"main\"
+            <town> ::= <town>"
+            .parse()
+            .unwrap();
+
+        assert_eq!(grammar.generate_from("street"), Ok(String::from("main")));
+        assert_eq!(
+            grammar.generate_from("address"),
+            Err(Error::Unproductive(vec![
+                String::from("address"),
+                String::from("town")
+            ]))
+        );
+        assert_eq!(
+            grammar.generate_from("road"),
+            Err(Error::UndefinedNonterminal(String::from("road")))
+        );
+        assert_eq!(
+            grammar.generate_from("\",\""),
+            Err(Error::UndefinedNonterminal(String::from("\",\"")))
+        );
+    }
+
+    #[test]
+    fn clone_builds_its_own_compiled() {
+        let grammar: Grammar = "<a> ::= \"x\"".parse().unwrap();
+        grammar.compiled();
+        let cloned = grammar.clone();
+
+        assert!(grammar.compiled.get().is_some());
+        assert!(cloned.compiled.get().is_none());
+        assert_eq!(cloned.compile(), grammar.compile());
+    }
+
+    #[test]
+    fn compiled_follows_changes() {
+        let mut grammar: Grammar = "<a> ::= <b>
+            <b> ::= \"x\""
+            .parse()
+            .unwrap();
+        let expressions_of = |grammar: &Grammar, name: &str| {
+            let compiled = grammar.compile();
+            let expressions = compiled
+                .symbols()
+                .nonterminal(name)
+                .map_or(vec![], |nt| compiled.expressions(nt).collect());
+            expressions
+                .into_iter()
+                .map(|expression| {
+                    expression
+                        .iter()
+                        .map(|&term| compiled.symbols().term(term).to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(expressions_of(&grammar, "b"), vec!["\"x\""]);
+
+        let extra: Production = "<b> ::= \"y\"".parse().unwrap();
+        grammar.add_production(extra.clone());
+        assert_eq!(expressions_of(&grammar, "b"), vec!["\"x\"", "\"y\""]);
+
+        let first = grammar.productions_iter().next().unwrap().clone();
+        grammar.remove_production(&first);
+        assert_eq!(expressions_of(&grammar, "a"), Vec::<String>::new());
+        assert_eq!(expressions_of(&grammar, "b"), vec!["\"x\"", "\"y\""]);
+
+        for production in grammar.productions_iter_mut() {
+            if *production == extra {
+                production.lhs = Term::Nonterminal(String::from("c"));
+            }
         }
+        assert_eq!(expressions_of(&grammar, "b"), vec!["\"x\""]);
+        assert_eq!(expressions_of(&grammar, "c"), vec!["\"y\""]);
+        assert_eq!(grammar.generate(), Ok(String::from("x")));
+        assert_eq!(grammar, grammar.clone());
     }
 
     #[test]
@@ -109,9 +109,18 @@
 //!
 //! If the generate function can't find a production for a nonterminal it tries
 //! to evaluate it will produce the identifer as is, i.e. `<identifier>`.
+//! `grammar.generate_with(rng, options)` takes `GenerateOptions` to skip such
+//! nonterminals or fail on them instead, to limit depth and length, and to
+//! draw from any `rand::Rng`.
 //!
-//! The generate function will return an error if it detects an infinite loop
-//! caused by a production such as `<PATTERN> ::= <PATTERN>`.
+//! The generate function will return an error naming the nonterminals it could
+//! never finish expanding, such as `<PATTERN>` in `<PATTERN> ::= <PATTERN>`.
+//! `grammar.generate_bounded(max_depth)` instead limits how deeply productions
+//! are expanded, steering recursive grammars toward short sentences.
+//!
+//! Sentences are generated from the lhs of the first production, unless a
+//! `%start <name>` line ahead of the productions declares another start
+//! symbol. `grammar.generate_from("name")` starts from any other nonterminal.
 //!
 //! ## Parse Example
 //!
@@ -162,18 +171,61 @@
 //! }
 //! ```
 //!
+//! ## Parse Input Example
+//!
+//! ```rust
+//! extern crate bnf;
+//! use bnf::Grammar;
+//!
+//! fn main() {
+//!     let input =
+//!         "<dna> ::= <base> | <base> <dna>
+//!         <base> ::= \"A\" | \"C\" | \"G\" | \"T\"";
+//!     let grammar: Grammar = input.parse().unwrap();
+//!     let parse_tree = grammar.parse_input("GATTACA");
+//!     match parse_tree {
+//!         Ok(tree) => println!("parse tree:\n{}", tree),
+//!         Err(e) => println!("not a DNA sequence: {}!", e)
+//!     }
+//! }
+//! ```
+//!
 
 extern crate nom;
 extern crate rand;
 extern crate stacker;
+mod abnf;
+mod analysis;
+mod compiled;
+mod earley;
+mod ebnf;
 mod error;
 mod expression;
+mod fresh;
+mod generate;
 mod grammar;
+mod ll1;
+mod lr;
+mod normal_form;
+mod parse_forest;
+mod parse_tree;
 mod parsers;
 mod production;
+mod span;
 mod term;
-pub use error::Error;
+mod transform;
+mod validate;
+pub use analysis::GrammarAnalysis;
+pub use compiled::{CompiledGrammar, Symbol, SymbolTable};
+pub use error::{Error, ParseError};
 pub use expression::Expression;
-pub use grammar::Grammar;
+pub use generate::{GenerateOptions, UndefinedPolicy};
+pub use grammar::{DuplicatePolicy, Grammar};
+pub use ll1::{PredictionConflict, PredictionTable};
+pub use lr::{Action, ConflictKind, LrConflict, LrItem, LrTable};
+pub use parse_forest::{ParseForest, Trees};
+pub use parse_tree::{ParseTree, ParseTreeNode};
 pub use production::Production;
+pub use span::{Position, Span};
 pub use term::Term;
+pub use validate::ValidationReport;
@@ -0,0 +1,175 @@
+use analysis::GrammarAnalysis;
+use expression::Expression;
+use grammar::Grammar;
+use std::collections::BTreeMap;
+use std::fmt;
+use term::Term;
+
+/// A PredictionConflict is an LL(1) table cell with several candidate
+/// expressions, so one token of lookahead cannot choose between them
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PredictionConflict<'gram> {
+    pub nonterminal: String,
+    /// The lookahead terminal, `""` standing for the end of input
+    pub lookahead: String,
+    /// The competing expressions, in grammar order
+    pub expressions: Vec<&'gram Expression>,
+}
+
+impl<'gram> fmt::Display for PredictionConflict<'gram> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lookahead = if self.lookahead.is_empty() {
+            String::from("end of input")
+        } else {
+            Term::Terminal(self.lookahead.clone()).to_string()
+        };
+        let expressions = self
+            .expressions
+            .iter()
+            .map(|expression| expression.to_string())
+            .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "{} on {}: {}",
+            Term::Nonterminal(self.nonterminal.clone()),
+            lookahead,
+            expressions.join(" | ")
+        )
+    }
+}
+
+/// A PredictionTable is an LL(1) parse table, choosing an expression for a
+/// nonterminal from the next terminal of input
+///
+/// Lookaheads are whole terminals, with `""` standing for the end of input.
+/// Every cell holding more than one expression is reported as a conflict.
+#[derive(Clone, Debug)]
+pub struct PredictionTable<'gram> {
+    cells: BTreeMap<(String, String), Vec<&'gram Expression>>,
+}
+
+impl<'gram> PredictionTable<'gram> {
+    /// Build the table for `grammar`
+    pub(crate) fn new(grammar: &'gram Grammar) -> PredictionTable<'gram> {
+        let analysis = GrammarAnalysis::new(grammar);
+        let mut cells: BTreeMap<(String, String), Vec<&Expression>> = BTreeMap::new();
+
+        for production in grammar.productions_iter() {
+            let lhs = match production.lhs {
+                Term::Nonterminal(ref nt) => nt.as_str(),
+                Term::Terminal(_) => continue,
+            };
+            for expression in production.rhs_iter() {
+                let mut lookaheads = analysis.first_of(expression.terms_iter());
+                if lookaheads.remove("") {
+                    if let Some(follow) = analysis.follow(lhs) {
+                        lookaheads.extend(follow.iter().cloned());
+                    }
+                }
+                for lookahead in lookaheads {
+                    cells
+                        .entry((lhs.to_string(), lookahead))
+                        .or_default()
+                        .push(expression);
+                }
+            }
+        }
+
+        PredictionTable { cells }
+    }
+
+    /// The expressions predicted for `nonterminal` when `lookahead` is next,
+    /// more than one meaning a conflict
+    pub fn predict(&self, nonterminal: &str, lookahead: &str) -> &[&'gram Expression] {
+        self.cells
+            .get(&(nonterminal.to_string(), lookahead.to_string()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Every conflict, ordered by nonterminal and then lookahead
+    pub fn conflicts(&self) -> Vec<PredictionConflict<'gram>> {
+        self.cells
+            .iter()
+            .filter(|&(_, expressions)| expressions.len() > 1)
+            .map(
+                |((nonterminal, lookahead), expressions)| PredictionConflict {
+                    nonterminal: nonterminal.clone(),
+                    lookahead: lookahead.clone(),
+                    expressions: expressions.clone(),
+                },
+            )
+            .collect()
+    }
+
+    /// Whether no cell has more than one expression, so the grammar is LL(1)
+    pub fn is_ll1(&self) -> bool {
+        self.cells
+            .values()
+            .all(|expressions| expressions.len() <= 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ll1_grammar() {
+        let grammar: Grammar = "<expr> ::= <term> <expr-rest>
+            <expr-rest> ::= \"+\" <term> <expr-rest> | \"\"
+            <term> ::= \"(\" <expr> \")\" | \"id\""
+            .parse()
+            .unwrap();
+        let table = grammar.ll1_table();
+
+        assert!(table.is_ll1());
+        assert!(table.conflicts().is_empty());
+        assert_eq!(table.predict("expr", "id").len(), 1);
+        assert_eq!(table.predict("expr", "+"), &[] as &[&Expression]);
+        assert_eq!(
+            table.predict("expr-rest", "+")[0].to_string(),
+            "\"+\" <term> <expr-rest>"
+        );
+        assert_eq!(table.predict("expr-rest", ")")[0].to_string(), "\"\"");
+        assert_eq!(table.predict("expr-rest", "")[0].to_string(), "\"\"");
+        assert!(table.predict("undefined", "id").is_empty());
+    }
+
+    #[test]
+    fn reports_conflicts() {
+        let grammar: Grammar = "<s> ::= <a> \"x\" | \"x\" | <s> \"y\"
+            <a> ::= \"\" | \"a\""
+            .parse()
+            .unwrap();
+        let table = grammar.ll1_table();
+        let conflicts = table.conflicts();
+
+        assert!(!table.is_ll1());
+        assert_eq!(
+            conflicts
+                .iter()
+                .map(|conflict| conflict.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "<s> on \"a\": <a> \"x\" | <s> \"y\"",
+                "<s> on \"x\": <a> \"x\" | \"x\" | <s> \"y\"",
+            ]
+        );
+        assert_eq!(conflicts[0].nonterminal, "s");
+        assert_eq!(conflicts[0].lookahead, "a");
+        assert_eq!(conflicts[1].expressions.len(), 3);
+    }
+
+    #[test]
+    fn end_of_input_conflict() {
+        let grammar: Grammar = "<s> ::= <a> | \"\"
+            <a> ::= \"\""
+            .parse()
+            .unwrap();
+        let conflicts = grammar.ll1_table().conflicts();
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "<s> on end of input: <a> | \"\"");
+    }
+}
@@ -0,0 +1,701 @@
+use analysis::GrammarAnalysis;
+use expression::Expression;
+use fresh::FreshNames;
+use grammar::Grammar;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::iter;
+use std::ptr;
+use term::Term;
+
+/// An LrItem is a rule with a dot marking how many of its terms have been
+/// matched. Empty terminals `""` are left out of `terms`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct LrItem {
+    pub lhs: String,
+    pub terms: Vec<Term>,
+    pub dot: usize,
+}
+
+impl fmt::Display for LrItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut terms = self
+            .terms
+            .iter()
+            .map(|term| term.to_string())
+            .collect::<Vec<_>>();
+        terms.insert(self.dot, String::from("•"));
+
+        write!(
+            f,
+            "{} ::= {}",
+            Term::Nonterminal(self.lhs.clone()),
+            terms.join(" ")
+        )
+    }
+}
+
+/// An Action is an entry of an LR ACTION table
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action<'gram> {
+    /// Consume the lookahead and move to a state
+    Shift(usize),
+    /// Replace the matched terms of an expression with its lhs nonterminal
+    Reduce(&'gram str, &'gram Expression),
+    /// The input is a sentence of the grammar
+    Accept,
+}
+
+impl<'gram> fmt::Display for Action<'gram> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Shift(state) => write!(f, "shift {}", state),
+            Action::Reduce(lhs, expression) => write!(
+                f,
+                "reduce {} ::= {}",
+                Term::Nonterminal(lhs.to_string()),
+                expression
+            ),
+            Action::Accept => write!(f, "accept"),
+        }
+    }
+}
+
+/// Whether a conflict is between a shift and reductions, or only reductions
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+/// An LrConflict is an ACTION table cell with several actions, along with
+/// the items of the state which call for them
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LrConflict<'gram> {
+    pub state: usize,
+    /// The lookahead terminal, `""` standing for the end of input
+    pub lookahead: String,
+    pub kind: ConflictKind,
+    pub actions: Vec<Action<'gram>>,
+    pub items: Vec<LrItem>,
+}
+
+impl<'gram> fmt::Display for LrConflict<'gram> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::ShiftReduce => "shift/reduce",
+            ConflictKind::ReduceReduce => "reduce/reduce",
+        };
+        let lookahead = if self.lookahead.is_empty() {
+            String::from("end of input")
+        } else {
+            Term::Terminal(self.lookahead.clone()).to_string()
+        };
+        let items = self
+            .items
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "{} conflict in state {} on {}: {}",
+            kind,
+            self.state,
+            lookahead,
+            items.join(", ")
+        )
+    }
+}
+
+/// Which lookaheads an LR table reduces on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum LrKind {
+    /// Every terminal
+    Lr0,
+    /// The FOLLOW set of the reduced nonterminal
+    Slr1,
+    /// Lookaheads propagated through the LR(0) automaton
+    Lalr1,
+}
+
+/// A single alternative of a `Production`, or the augmented start rule
+struct Rule<'gram> {
+    lhs: String,
+    terms: Vec<Term>,
+    /// The production lhs and expression this rule came from, `None` for the
+    /// augmented start rule
+    source: Option<(&'gram str, &'gram Expression)>,
+}
+
+/// An item of a rule, `(rule, dot)`
+type Item = (usize, usize);
+
+/// An LrTable holds the ACTION and GOTO tables of an LR automaton built over
+/// the LR(0) item sets of a `Grammar`, starting from its start symbol
+///
+/// Lookaheads are whole terminals, with `""` standing for the end of input,
+/// and empty terminals `""` in expressions match the empty string.
+pub struct LrTable<'gram> {
+    rules: Vec<Rule<'gram>>,
+    /// The closed item set of each state
+    states: Vec<Vec<Item>>,
+    actions: Vec<BTreeMap<String, Vec<Action<'gram>>>>,
+    gotos: Vec<BTreeMap<String, usize>>,
+}
+
+impl<'gram> LrTable<'gram> {
+    pub(crate) fn new(grammar: &'gram Grammar, kind: LrKind) -> LrTable<'gram> {
+        let start = match grammar.start_symbol() {
+            Some(start) => start,
+            _ => {
+                return LrTable {
+                    rules: vec![],
+                    states: vec![],
+                    actions: vec![],
+                    gotos: vec![],
+                }
+            }
+        };
+
+        // avoid every nonterminal, even those referenced but left undefined
+        let mut names = FreshNames::new(
+            grammar
+                .productions_iter()
+                .flat_map(|p| iter::once(&p.lhs).chain(p.rhs_iter().flat_map(|e| e.terms_iter())))
+                .filter_map(|term| match *term {
+                    Term::Nonterminal(ref nt) => Some(nt.clone()),
+                    Term::Terminal(_) => None,
+                }),
+        );
+        let mut rules = vec![Rule {
+            lhs: names.fresh(&format!("{}'", start)),
+            terms: vec![Term::Nonterminal(start.to_string())],
+            source: None,
+        }];
+        for production in grammar.productions_iter() {
+            let lhs = match production.lhs {
+                Term::Nonterminal(ref nt) => nt,
+                Term::Terminal(_) => continue,
+            };
+            for expression in production.rhs_iter() {
+                rules.push(Rule {
+                    lhs: lhs.clone(),
+                    terms: expression
+                        .terms_iter()
+                        .filter(|term| **term != Term::Terminal(String::new()))
+                        .cloned()
+                        .collect(),
+                    source: Some((lhs, expression)),
+                });
+            }
+        }
+
+        let mut table = LrTable {
+            rules,
+            states: vec![],
+            actions: vec![],
+            gotos: vec![],
+        };
+        let automaton = Automaton::new(&table.rules);
+        let analysis = GrammarAnalysis::new(grammar);
+        let lookaheads = match kind {
+            LrKind::Lr0 => automaton.lr0_lookaheads(&table.rules),
+            LrKind::Slr1 => automaton.slr1_lookaheads(&table.rules, &analysis),
+            LrKind::Lalr1 => automaton.lalr1_lookaheads(&table.rules, &analysis),
+        };
+
+        for (state, closure) in automaton.closures.iter().enumerate() {
+            let mut actions: BTreeMap<String, Vec<Action>> = BTreeMap::new();
+            let mut gotos = BTreeMap::new();
+            for &(ref symbol, target) in &automaton.transitions[state] {
+                match *symbol {
+                    Term::Terminal(ref t) => actions
+                        .entry(t.clone())
+                        .or_default()
+                        .push(Action::Shift(target)),
+                    Term::Nonterminal(ref nt) => {
+                        gotos.insert(nt.clone(), target);
+                    }
+                }
+            }
+            for &(item, ref item_lookaheads) in &lookaheads[state] {
+                let action = match table.rules[item.0].source {
+                    Some((lhs, expression)) => Action::Reduce(lhs, expression),
+                    None => Action::Accept,
+                };
+                for lookahead in item_lookaheads {
+                    if action == Action::Accept && !lookahead.is_empty() {
+                        continue;
+                    }
+                    actions.entry(lookahead.clone()).or_default().push(action);
+                }
+            }
+
+            table.states.push(closure.clone());
+            table.actions.push(actions);
+            table.gotos.push(gotos);
+        }
+
+        table
+    }
+
+    /// The number of states, numbered from the start state 0
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Every item of `state`, kernel items first
+    pub fn items(&self, state: usize) -> Vec<LrItem> {
+        self.states.get(state).map_or(vec![], |items| {
+            items.iter().map(|&item| self.item(item)).collect()
+        })
+    }
+
+    fn item(&self, (rule, dot): Item) -> LrItem {
+        LrItem {
+            lhs: self.rules[rule].lhs.clone(),
+            terms: self.rules[rule].terms.clone(),
+            dot,
+        }
+    }
+
+    /// The actions for `state` when `lookahead` is next, more than one
+    /// meaning a conflict
+    pub fn action(&self, state: usize, lookahead: &str) -> &[Action<'gram>] {
+        self.actions
+            .get(state)
+            .and_then(|actions| actions.get(lookahead))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The state to move to from `state` after reducing to `nonterminal`
+    pub fn goto(&self, state: usize, nonterminal: &str) -> Option<usize> {
+        self.gotos
+            .get(state)
+            .and_then(|gotos| gotos.get(nonterminal))
+            .cloned()
+    }
+
+    /// Every conflict, ordered by state and then lookahead
+    pub fn conflicts(&self) -> Vec<LrConflict<'gram>> {
+        let mut conflicts = vec![];
+        for (state, actions) in self.actions.iter().enumerate() {
+            for (lookahead, actions) in actions.iter().filter(|&(_, a)| a.len() > 1) {
+                let kind = if actions.iter().any(|a| matches!(*a, Action::Shift(_))) {
+                    ConflictKind::ShiftReduce
+                } else {
+                    ConflictKind::ReduceReduce
+                };
+                let items = self.states[state]
+                    .iter()
+                    .filter(|&&item| self.calls_for(item, lookahead, actions))
+                    .map(|&item| self.item(item))
+                    .collect();
+
+                conflicts.push(LrConflict {
+                    state,
+                    lookahead: lookahead.clone(),
+                    kind,
+                    actions: actions.clone(),
+                    items,
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Whether `item` is the reason for one of `actions` on `lookahead`
+    fn calls_for(&self, (rule, dot): Item, lookahead: &str, actions: &[Action<'gram>]) -> bool {
+        let rule = &self.rules[rule];
+        match rule.terms.get(dot) {
+            Some(Term::Terminal(t)) => t == lookahead,
+            Some(Term::Nonterminal(_)) => false,
+            None => actions.iter().any(|action| match (*action, rule.source) {
+                (Action::Reduce(_, reduced), Some((_, expression))) => ptr::eq(reduced, expression),
+                (Action::Accept, None) => true,
+                _ => false,
+            }),
+        }
+    }
+
+    /// Whether no ACTION cell has more than one action
+    pub fn is_conflict_free(&self) -> bool {
+        self.actions
+            .iter()
+            .all(|actions| actions.values().all(|actions| actions.len() <= 1))
+    }
+}
+
+/// Complete items of a state, with the lookaheads to reduce them on
+type Reductions = Vec<(Item, BTreeSet<String>)>;
+
+/// The LR(0) automaton: canonical item sets and their transitions
+struct Automaton {
+    by_lhs: HashMap<String, Vec<usize>>,
+    kernels: Vec<Vec<Item>>,
+    closures: Vec<Vec<Item>>,
+    transitions: Vec<Vec<(Term, usize)>>,
+}
+
+impl Automaton {
+    fn new(rules: &[Rule]) -> Automaton {
+        let mut by_lhs: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, rule) in rules.iter().enumerate().skip(1) {
+            by_lhs.entry(rule.lhs.clone()).or_default().push(index);
+        }
+
+        let mut automaton = Automaton {
+            by_lhs,
+            kernels: vec![vec![(0, 0)]],
+            closures: vec![],
+            transitions: vec![],
+        };
+        let mut states: HashMap<Vec<Item>, usize> = HashMap::new();
+        states.insert(vec![(0, 0)], 0);
+
+        let mut state = 0;
+        while state < automaton.kernels.len() {
+            let closure = automaton.closure(rules, &automaton.kernels[state]);
+
+            // group advanced items by the symbol after the dot, in order of appearance
+            let mut advanced: Vec<(Term, Vec<Item>)> = vec![];
+            for &(rule, dot) in &closure {
+                if let Some(symbol) = rules[rule].terms.get(dot) {
+                    match advanced.iter_mut().find(|(s, _)| s == symbol) {
+                        Some(&mut (_, ref mut kernel)) => kernel.push((rule, dot + 1)),
+                        None => advanced.push((symbol.clone(), vec![(rule, dot + 1)])),
+                    }
+                }
+            }
+
+            let mut transitions = vec![];
+            for (symbol, mut kernel) in advanced {
+                kernel.sort();
+                kernel.dedup();
+                let next = automaton.kernels.len();
+                let target = *states.entry(kernel.clone()).or_insert(next);
+                if target == next {
+                    automaton.kernels.push(kernel);
+                }
+                transitions.push((symbol, target));
+            }
+
+            automaton.closures.push(closure);
+            automaton.transitions.push(transitions);
+            state += 1;
+        }
+
+        automaton
+    }
+
+    fn closure(&self, rules: &[Rule], kernel: &[Item]) -> Vec<Item> {
+        let mut closure = kernel.to_vec();
+        let mut seen: HashSet<Item> = kernel.iter().cloned().collect();
+        let mut next = 0;
+        while next < closure.len() {
+            let (rule, dot) = closure[next];
+            next += 1;
+            if let Some(Term::Nonterminal(nt)) = rules[rule].terms.get(dot) {
+                for &predicted in self.by_lhs.get(nt).map_or(&[][..], Vec::as_slice) {
+                    if seen.insert((predicted, 0)) {
+                        closure.push((predicted, 0));
+                    }
+                }
+            }
+        }
+        closure
+    }
+
+    fn target(&self, state: usize, symbol: &Term) -> usize {
+        self.transitions[state]
+            .iter()
+            .find(|(s, _)| s == symbol)
+            .map(|&(_, target)| target)
+            .expect("transition on the symbol after a dot")
+    }
+
+    /// Complete items of each state, each with `lookaheads(state, item)`
+    fn reductions<F>(&self, rules: &[Rule], mut lookaheads: F) -> Vec<Reductions>
+    where
+        F: FnMut(usize, Item) -> BTreeSet<String>,
+    {
+        self.closures
+            .iter()
+            .enumerate()
+            .map(|(state, closure)| {
+                closure
+                    .iter()
+                    .filter(|&&(rule, dot)| dot == rules[rule].terms.len())
+                    .map(|&item| (item, lookaheads(state, item)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn lr0_lookaheads(&self, rules: &[Rule]) -> Vec<Reductions> {
+        let mut terminals: BTreeSet<String> = rules
+            .iter()
+            .flat_map(|rule| rule.terms.iter())
+            .filter_map(|term| match *term {
+                Term::Terminal(ref t) => Some(t.clone()),
+                Term::Nonterminal(_) => None,
+            })
+            .collect();
+        terminals.insert(String::new());
+
+        self.reductions(rules, |_, _| terminals.clone())
+    }
+
+    fn slr1_lookaheads(&self, rules: &[Rule], analysis: &GrammarAnalysis) -> Vec<Reductions> {
+        self.reductions(rules, |_, (rule, _)| match rules[rule].source {
+            Some((lhs, _)) => analysis.follow(lhs).cloned().unwrap_or_default(),
+            None => Some(String::new()).into_iter().collect(),
+        })
+    }
+
+    /// LR(1) closure of items with lookaheads, `None` standing for a lookahead
+    /// still to be propagated
+    fn closure1(
+        &self,
+        rules: &[Rule],
+        analysis: &GrammarAnalysis,
+        kernel: Vec<(Item, Option<String>)>,
+    ) -> Vec<(Item, Option<String>)> {
+        let mut seen: HashSet<(Item, Option<String>)> = kernel.iter().cloned().collect();
+        let mut closure = kernel;
+        let mut next = 0;
+        while next < closure.len() {
+            let ((rule, dot), lookahead) = closure[next].clone();
+            next += 1;
+            let nt = match rules[rule].terms.get(dot) {
+                Some(Term::Nonterminal(nt)) => nt,
+                _ => continue,
+            };
+            let first = analysis.first_of(rules[rule].terms[dot + 1..].iter());
+            let lookaheads = first.into_iter().map(|terminal| {
+                if terminal.is_empty() {
+                    lookahead.clone()
+                } else {
+                    Some(terminal)
+                }
+            });
+            for lookahead in lookaheads {
+                for &predicted in self.by_lhs.get(nt).map_or(&[][..], Vec::as_slice) {
+                    let item = ((predicted, 0), lookahead.clone());
+                    if seen.insert(item.clone()) {
+                        closure.push(item);
+                    }
+                }
+            }
+        }
+        closure
+    }
+
+    fn lalr1_lookaheads(&self, rules: &[Rule], analysis: &GrammarAnalysis) -> Vec<Reductions> {
+        // lookaheads of each kernel item, and the kernel items they flow on to
+        let mut lookaheads: Vec<Vec<BTreeSet<String>>> = self
+            .kernels
+            .iter()
+            .map(|kernel| vec![BTreeSet::new(); kernel.len()])
+            .collect();
+        let mut propagates: Vec<Vec<Vec<(usize, usize)>>> = self
+            .kernels
+            .iter()
+            .map(|kernel| vec![vec![]; kernel.len()])
+            .collect();
+        lookaheads[0][0].insert(String::new());
+
+        for (state, kernel) in self.kernels.iter().enumerate() {
+            for (index, &item) in kernel.iter().enumerate() {
+                for ((rule, dot), lookahead) in self.closure1(rules, analysis, vec![(item, None)]) {
+                    let symbol = match rules[rule].terms.get(dot) {
+                        Some(symbol) => symbol,
+                        None => continue,
+                    };
+                    let target = self.target(state, symbol);
+                    let target_index = self.kernels[target]
+                        .iter()
+                        .position(|&k| k == (rule, dot + 1))
+                        .expect("advanced item in target kernel");
+                    match lookahead {
+                        Some(terminal) => {
+                            lookaheads[target][target_index].insert(terminal);
+                        }
+                        None => propagates[state][index].push((target, target_index)),
+                    }
+                }
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (state, kernel_propagates) in propagates.iter().enumerate() {
+                for (index, targets) in kernel_propagates.iter().enumerate() {
+                    for &(target, target_index) in targets {
+                        let from = lookaheads[state][index].clone();
+                        let to = &mut lookaheads[target][target_index];
+                        for terminal in from {
+                            changed |= to.insert(terminal);
+                        }
+                    }
+                }
+            }
+        }
+
+        // complete items outside the kernel come from empty expressions, and
+        // get their lookaheads from closing over the kernel's
+        self.kernels
+            .iter()
+            .enumerate()
+            .map(|(state, kernel)| {
+                let kernel = kernel
+                    .iter()
+                    .zip(&lookaheads[state])
+                    .flat_map(|(&item, terminals)| {
+                        terminals.iter().map(move |t| (item, Some(t.clone())))
+                    })
+                    .collect();
+                let mut reductions: BTreeMap<Item, BTreeSet<String>> = BTreeMap::new();
+                for ((rule, dot), lookahead) in self.closure1(rules, analysis, kernel) {
+                    if dot == rules[rule].terms.len() {
+                        let terminals = reductions.entry((rule, dot)).or_default();
+                        terminals.extend(lookahead);
+                    }
+                }
+                reductions.into_iter().collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conflicts(table: &LrTable) -> Vec<String> {
+        table
+            .conflicts()
+            .iter()
+            .map(|conflict| conflict.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn expression_grammar() {
+        let grammar: Grammar = "<e> ::= <e> \"+\" <t> | <t>
+            <t> ::= <t> \"*\" <f> | <f>
+            <f> ::= \"(\" <e> \")\" | \"id\""
+            .parse()
+            .unwrap();
+
+        let lr0 = grammar.lr0_table();
+        assert_eq!(lr0.state_count(), 12);
+        assert_eq!(
+            conflicts(&lr0),
+            vec![
+                "shift/reduce conflict in state 2 on \"*\": <e> ::= <t> •, <t> ::= <t> • \"*\" <f>",
+                "shift/reduce conflict in state 9 on \"*\": <e> ::= <e> \"+\" <t> •, <t> ::= <t> • \"*\" <f>",
+            ]
+        );
+
+        for table in &[grammar.slr1_table(), grammar.lalr1_table()] {
+            assert!(table.is_conflict_free(), "{:?}", conflicts(table));
+            assert_eq!(table.state_count(), 12);
+            assert_eq!(table.action(0, "id"), &[Action::Shift(5)]);
+            assert_eq!(table.action(1, ""), &[Action::Accept]);
+            assert_eq!(table.goto(0, "t"), Some(2));
+            assert_eq!(table.goto(0, "id"), None);
+            assert!(table.action(0, "+").is_empty());
+            match table.action(2, "+") {
+                [Action::Reduce("e", expression)] => assert_eq!(expression.to_string(), "<t>"),
+                actions => panic!("should reduce <e> ::= <t>: {:?}", actions),
+            }
+        }
+    }
+
+    #[test]
+    fn lalr_but_not_slr() {
+        let grammar: Grammar = "<s> ::= <l> \"=\" <r> | <r>
+            <l> ::= \"*\" <r> | \"id\"
+            <r> ::= <l>"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            conflicts(&grammar.slr1_table()),
+            vec![
+                "shift/reduce conflict in state 2 on \"=\": <s> ::= <l> • \"=\" <r>, <r> ::= <l> •"
+            ]
+        );
+        assert!(grammar.lalr1_table().is_conflict_free());
+
+        let conflict = &grammar.slr1_table().conflicts()[0];
+        assert_eq!(conflict.kind, ConflictKind::ShiftReduce);
+        assert_eq!(conflict.actions.len(), 2);
+    }
+
+    #[test]
+    fn reduce_reduce_conflict() {
+        let grammar: Grammar = "<s> ::= <a> \"x\" | <b> \"x\"
+            <a> ::= \"a\"
+            <b> ::= \"a\""
+            .parse()
+            .unwrap();
+        let conflicts = grammar.lalr1_table().conflicts();
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::ReduceReduce);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "reduce/reduce conflict in state 4 on \"x\": <a> ::= \"a\" •, <b> ::= \"a\" •"
+        );
+    }
+
+    #[test]
+    fn empty_expressions() {
+        let grammar: Grammar = "<s> ::= <a> \"b\"
+            <a> ::= \"\" | \"a\""
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            conflicts(&grammar.lr0_table()),
+            vec!["shift/reduce conflict in state 0 on \"a\": <a> ::= •, <a> ::= • \"a\""]
+        );
+        for table in &[grammar.slr1_table(), grammar.lalr1_table()] {
+            assert!(table.is_conflict_free());
+            match table.action(0, "b") {
+                [Action::Reduce("a", expression)] => assert_eq!(expression.to_string(), "\"\""),
+                actions => panic!("should reduce <a> ::= \"\": {:?}", actions),
+            }
+        }
+    }
+
+    #[test]
+    fn items_and_empty_grammar() {
+        let grammar: Grammar = "<s> ::= \"x\" <s> | \"\"".parse().unwrap();
+        let table = grammar.lalr1_table();
+
+        assert_eq!(
+            table
+                .items(0)
+                .iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>(),
+            vec!["<s'> ::= • <s>", "<s> ::= • \"x\" <s>", "<s> ::= •"]
+        );
+        assert!(table.items(100).is_empty());
+        assert!(table.is_conflict_free());
+
+        let primed: Grammar = "<s> ::= \"x\" <s'>".parse().unwrap();
+        assert_eq!(
+            primed.lr0_table().items(0)[0].to_string(),
+            "<s'-2> ::= • <s>"
+        );
+
+        let empty = Grammar::new();
+        assert_eq!(empty.lalr1_table().state_count(), 0);
+    }
+}
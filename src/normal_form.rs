@@ -0,0 +1,714 @@
+//! Conversions of a `Grammar` to normal forms, and the steps they share.
+
+use grammar::Grammar;
+use std::collections::{HashMap, HashSet};
+use term::Term;
+use transform::{push_unique, Alternative, Rules};
+
+/// Nonterminals which can derive the empty string
+pub(crate) fn nullable(rules: &Rules) -> HashSet<String> {
+    let mut nullable = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for name in &rules.names {
+            if nullable.contains(name) {
+                continue;
+            }
+            let derives_empty = rules.alternatives[name].iter().any(|alternative| {
+                alternative.iter().all(|term| match *term {
+                    Term::Nonterminal(ref nt) => nullable.contains(nt),
+                    Term::Terminal(_) => false,
+                })
+            });
+            if derives_empty {
+                nullable.insert(name.clone());
+                changed = true;
+            }
+        }
+    }
+    nullable
+}
+
+/// Give a nullable start nonterminal which appears on a right hand side a
+/// fresh replacement, so only the start derives the empty string directly
+fn separate_start(rules: &mut Rules) {
+    let start = match rules.names.first() {
+        Some(start) => start.clone(),
+        None => return,
+    };
+    let term = Term::Nonterminal(start.clone());
+    let on_rhs = rules
+        .alternatives
+        .values()
+        .flat_map(|alternatives| alternatives.iter())
+        .any(|alternative| alternative.contains(&term));
+    if !on_rhs || !nullable(rules).contains(&start) {
+        return;
+    }
+
+    let fresh = rules.fresh.fresh(&format!("{}-start", start));
+    if rules.declared_start.is_some() {
+        rules.declared_start = Some(fresh.clone());
+    }
+    rules.names.insert(0, fresh.clone());
+    rules.alternatives.insert(fresh, vec![vec![term]]);
+}
+
+/// Replace terminals in alternatives of more than one term with fresh
+/// nonterminals deriving just that terminal
+fn isolate_terminals(rules: &mut Rules) {
+    let mut isolated: HashMap<String, String> = HashMap::new();
+    for name in rules.names.clone() {
+        let mut alternatives = rules.alternatives.remove(&name).unwrap_or_default();
+        for alternative in alternatives.iter_mut().filter(|a| a.len() > 1) {
+            for term in alternative.iter_mut() {
+                let terminal = match *term {
+                    Term::Terminal(ref t) => t.clone(),
+                    Term::Nonterminal(_) => continue,
+                };
+                let nonterminal = match isolated.get(&terminal) {
+                    Some(nonterminal) => nonterminal.clone(),
+                    None => {
+                        let nonterminal = rules.fresh.fresh(&terminal);
+                        rules.names.push(nonterminal.clone());
+                        rules.alternatives.insert(
+                            nonterminal.clone(),
+                            vec![vec![Term::Terminal(terminal.clone())]],
+                        );
+                        isolated.insert(terminal, nonterminal.clone());
+                        nonterminal
+                    }
+                };
+                *term = Term::Nonterminal(nonterminal);
+            }
+        }
+        rules.alternatives.insert(name, alternatives);
+    }
+}
+
+/// Split alternatives of more than two terms into chains of fresh
+/// nonterminals, each with two terms
+fn binarize(rules: &mut Rules) {
+    for name in rules.names.clone() {
+        let mut alternatives = rules.alternatives.remove(&name).unwrap_or_default();
+        let mut after = name.clone();
+        for alternative in alternatives.iter_mut().filter(|a| a.len() > 2) {
+            let chain: Vec<String> = (2..alternative.len())
+                .map(|_| rules.fresh.fresh(&format!("{}-rest", name)))
+                .collect();
+            let terms = alternative.split_off(1);
+            alternative.push(Term::Nonterminal(chain[0].clone()));
+
+            for (index, link) in chain.iter().enumerate() {
+                let rest = match chain.get(index + 1) {
+                    Some(next) => vec![terms[index].clone(), Term::Nonterminal(next.clone())],
+                    None => terms[index..].to_vec(),
+                };
+                rules.insert(link.clone(), vec![rest], Some(&after));
+                after = link.clone();
+            }
+        }
+        rules.alternatives.insert(name, alternatives);
+    }
+}
+
+/// Remove empty alternatives, adding every way of leaving out nullable
+/// nonterminals in their place. The start nonterminal keeps an empty
+/// alternative if it was nullable, which is returned.
+pub(crate) fn remove_empty(rules: &mut Rules) -> bool {
+    let nullable = nullable(rules);
+    let start = rules.names.first().cloned();
+
+    for name in &rules.names {
+        let alternatives = rules.alternatives.remove(name).unwrap_or_default();
+        let mut kept = vec![];
+        for alternative in alternatives {
+            let mut variants: Vec<Alternative> = vec![vec![]];
+            for term in alternative {
+                let optional = match term {
+                    Term::Nonterminal(ref nt) => nullable.contains(nt),
+                    Term::Terminal(_) => false,
+                };
+                if optional {
+                    let without = variants.clone();
+                    for variant in &mut variants {
+                        variant.push(term.clone());
+                    }
+                    variants.extend(without);
+                } else {
+                    for variant in &mut variants {
+                        variant.push(term.clone());
+                    }
+                }
+            }
+            for variant in variants {
+                let is_self = variant.len() == 1 && variant[0] == Term::Nonterminal(name.clone());
+                if !variant.is_empty() && !is_self {
+                    push_unique(&mut kept, variant);
+                }
+            }
+        }
+        rules.alternatives.insert(name.clone(), kept);
+    }
+
+    match start {
+        Some(start) if nullable.contains(&start) => {
+            let alternatives = rules.alternatives.get_mut(&start).expect("start");
+            alternatives.push(vec![]);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Replace alternatives which are a single nonterminal with that
+/// nonterminal's alternatives, following chains of them
+pub(crate) fn remove_units(rules: &mut Rules) {
+    let unit = |alternative: &Alternative| match alternative.as_slice() {
+        [Term::Nonterminal(nt)] => Some(nt.clone()),
+        _ => None,
+    };
+
+    let mut replaced = HashMap::new();
+    for name in &rules.names {
+        let mut reached = vec![name.clone()];
+        let mut next = 0;
+        while next < reached.len() {
+            let units: Vec<String> = rules
+                .alternatives
+                .get(&reached[next])
+                .map_or(vec![], |alternatives| {
+                    alternatives.iter().filter_map(&unit).collect()
+                });
+            next += 1;
+            for nt in units {
+                if !reached.contains(&nt) {
+                    reached.push(nt);
+                }
+            }
+        }
+
+        let mut alternatives = vec![];
+        for nt in reached {
+            for alternative in rules.alternatives.get(&nt).into_iter().flatten() {
+                if unit(alternative).is_none() {
+                    push_unique(&mut alternatives, alternative.clone());
+                }
+            }
+        }
+        replaced.insert(name.clone(), alternatives);
+    }
+    rules.alternatives = replaced;
+}
+
+/// Remove empty alternatives, returning whether the start nonterminal was
+/// nullable and so keeps one
+pub(crate) fn remove_epsilon_productions(grammar: &Grammar) -> (Grammar, bool) {
+    let mut rules = Rules::new(grammar);
+    separate_start(&mut rules);
+    let nullable_start = remove_empty(&mut rules);
+    rules.remove_empty_nonterminals();
+    (rules.into_grammar(), nullable_start)
+}
+
+/// Remove alternatives which are a single nonterminal
+pub(crate) fn remove_unit_productions(grammar: &Grammar) -> Grammar {
+    let mut rules = Rules::new(grammar);
+    remove_units(&mut rules);
+    rules.remove_empty_nonterminals();
+    rules.into_grammar()
+}
+
+/// Convert to Chomsky Normal Form by the START, TERM, BIN, DEL and UNIT steps
+pub(crate) fn to_cnf(grammar: &Grammar) -> Grammar {
+    let mut rules = Rules::new(grammar);
+    separate_start(&mut rules);
+    isolate_terminals(&mut rules);
+    binarize(&mut rules);
+    remove_empty(&mut rules);
+    remove_units(&mut rules);
+    rules.remove_empty_nonterminals();
+    rules.into_grammar()
+}
+
+/// Remove nonterminals which derive no string of terminals, including
+/// undefined ones, and every alternative using them. An unproductive start
+/// stays the start, so the grammar derives nothing.
+fn remove_unproductive(rules: &mut Rules) {
+    let mut productive: HashSet<String> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for name in &rules.names {
+            if productive.contains(name) {
+                continue;
+            }
+            let derives = rules.alternatives[name].iter().any(|alternative| {
+                alternative.iter().all(|term| match *term {
+                    Term::Nonterminal(ref nt) => productive.contains(nt),
+                    Term::Terminal(_) => true,
+                })
+            });
+            if derives {
+                productive.insert(name.clone());
+                changed = true;
+            }
+        }
+    }
+
+    // unproductive nonterminals are left without alternatives, and so
+    // removed, keeping an unproductive start as the declared start
+    for alternatives in rules.alternatives.values_mut() {
+        alternatives.retain(|alternative| {
+            alternative.iter().all(|term| match *term {
+                Term::Nonterminal(ref nt) => productive.contains(nt),
+                Term::Terminal(_) => true,
+            })
+        });
+    }
+    rules.remove_empty_nonterminals();
+}
+
+/// Replace alternatives starting with a nonterminal accepted by `expand`
+/// with that nonterminal's alternatives followed by the rest
+fn expand_leading<F>(rules: &Rules, alternatives: Vec<Alternative>, expand: F) -> Vec<Alternative>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut expanded = vec![];
+    for alternative in alternatives {
+        let leading = match alternative.first() {
+            Some(Term::Nonterminal(nt)) if expand(nt) => nt,
+            _ => {
+                push_unique(&mut expanded, alternative);
+                continue;
+            }
+        };
+        for prefix in rules.alternatives.get(leading).into_iter().flatten() {
+            let mut replaced = prefix.clone();
+            replaced.extend(alternative[1..].iter().cloned());
+            push_unique(&mut expanded, replaced);
+        }
+    }
+    expanded
+}
+
+/// Convert to Greibach Normal Form, returning whether the empty string had
+/// to be left out of the language
+pub(crate) fn to_gnf(grammar: &Grammar) -> (Grammar, bool) {
+    let mut rules = Rules::new(grammar);
+    let derives_empty = remove_empty(&mut rules);
+    if derives_empty {
+        let start = rules.names[0].clone();
+        let alternatives = rules.alternatives.get_mut(&start).expect("start");
+        alternatives.retain(|alternative| !alternative.is_empty());
+    }
+    remove_units(&mut rules);
+    remove_unproductive(&mut rules);
+
+    // afterwards every alternative of a nonterminal starts with a terminal,
+    // a later nonterminal, or (only for tails) any original nonterminal
+    let order = rules.names.clone();
+    let mut tails = vec![];
+    for (i, name) in order.iter().enumerate() {
+        let mut alternatives = rules.alternatives[name].clone();
+        for earlier in &order[..i] {
+            alternatives = expand_leading(&rules, alternatives, |nt| nt == earlier);
+        }
+
+        let term = Term::Nonterminal(name.clone());
+        let (recursive, others): (Vec<_>, Vec<_>) = alternatives
+            .into_iter()
+            .partition(|alternative| alternative.first() == Some(&term));
+        if recursive.is_empty() {
+            rules.alternatives.insert(name.clone(), others);
+            continue;
+        }
+
+        // <a> ::= <a> α | β becomes <a> ::= β | β <a-tail>, <a-tail> ::= α | α <a-tail>
+        let tail = rules.fresh.fresh(&format!("{}-tail", name));
+        let with_tail = |alternatives: Vec<Alternative>| {
+            let mut both = vec![];
+            for alternative in alternatives {
+                let mut extended = alternative.clone();
+                extended.push(Term::Nonterminal(tail.clone()));
+                both.push(alternative);
+                both.push(extended);
+            }
+            both
+        };
+        let rests = recursive.into_iter().map(|a| a[1..].to_vec()).collect();
+        let tail_alternatives = with_tail(rests);
+        rules.alternatives.insert(name.clone(), with_tail(others));
+        rules.insert(tail.clone(), tail_alternatives, Some(name));
+        tails.push(tail);
+    }
+
+    // the last nonterminal now starts with terminals only, and each earlier
+    // one only needs later ones expanded
+    for name in order.iter().rev() {
+        let alternatives = rules.alternatives.remove(name).unwrap_or_default();
+        let expanded = expand_leading(&rules, alternatives, |nt| order.iter().any(|o| o == nt));
+        rules.alternatives.insert(name.clone(), expanded);
+    }
+    for tail in &tails {
+        let alternatives = rules.alternatives.remove(tail).unwrap_or_default();
+        let expanded = expand_leading(&rules, alternatives, |nt| order.iter().any(|o| o == nt));
+        rules.alternatives.insert(tail.clone(), expanded);
+    }
+
+    remove_unproductive(&mut rules);
+    (rules.into_grammar(), derives_empty)
+}
+
+/// Whether every expression is a single terminal or two nonterminals, and
+/// every production has one. The start nonterminal may also derive `""`, if
+/// it is on no right hand side.
+pub(crate) fn is_cnf(grammar: &Grammar) -> bool {
+    let start = match grammar.start_symbol() {
+        Some(start) => Term::Nonterminal(start.to_string()),
+        None => return true,
+    };
+    let derives_empty = grammar
+        .productions_iter()
+        .filter(|production| production.lhs == start)
+        .flat_map(|production| production.rhs_iter())
+        .any(|expression| {
+            expression
+                .terms_iter()
+                .eq(Some(&Term::Terminal(String::new())))
+        });
+
+    grammar.productions_iter().all(|production| {
+        if let Term::Terminal(_) = production.lhs {
+            return false;
+        }
+        if production.rhs_iter().next().is_none() {
+            return false;
+        }
+        production.rhs_iter().all(|expression| {
+            let terms = expression.terms_iter().collect::<Vec<_>>();
+            match terms.as_slice() {
+                [Term::Terminal(t)] => !t.is_empty() || production.lhs == start,
+                [b @ Term::Nonterminal(_), c @ Term::Nonterminal(_)] => {
+                    !derives_empty || (**b != start && **c != start)
+                }
+                _ => false,
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use production::Production;
+
+    fn assert_same_language(original: &Grammar, transformed: &Grammar, sentences: &[&str]) {
+        for sentence in sentences {
+            assert_eq!(
+                original.parse_input(sentence).is_ok(),
+                transformed.parse_input(sentence).is_ok(),
+                "{:?} in\n{}",
+                sentence,
+                transformed
+            );
+        }
+    }
+
+    #[test]
+    fn cnf_conversion() {
+        let grammar: Grammar = "<s> ::= <a> <s> <a> | \"a\" <b>
+            <a> ::= <b> | <s>
+            <b> ::= \"b\" | \"\""
+            .parse()
+            .unwrap();
+        let cnf = grammar.to_cnf();
+
+        assert!(!grammar.is_cnf());
+        assert!(cnf.is_cnf(), "{}", cnf);
+        assert_eq!(
+            cnf.to_string(),
+            "<s> ::= <a> <s-rest> | <a-2> <b> | <s> <a> | \"a\"
+<s-rest> ::= <s> <a> | <a> <s-rest> | <a-2> <b> | \"a\"
+<a> ::= \"b\" | <a> <s-rest> | <a-2> <b> | <s> <a> | \"a\"
+<b> ::= \"b\"
+<a-2> ::= \"a\"
+"
+        );
+        assert_same_language(
+            &grammar,
+            &cnf,
+            &[
+                "a", "ab", "b", "ba", "aab", "bab", "abab", "bbabb", "", "bb", "aa",
+            ],
+        );
+    }
+
+    #[test]
+    fn cnf_drops_nonterminals_without_alternatives() {
+        let grammar: Grammar = "<s> ::= <a>
+            <a> ::= \"\""
+            .parse()
+            .unwrap();
+        let cnf = grammar.to_cnf();
+
+        assert!(cnf.is_cnf(), "{}", cnf);
+        assert_eq!(cnf.to_string(), "<s> ::= \"\"\n");
+        assert_eq!(cnf.to_string().parse(), Ok(cnf));
+
+        let empty = Grammar::from_parts(vec![Production::from_parts(
+            Term::Nonterminal(String::from("s")),
+            vec![],
+        )]);
+        assert!(!empty.is_cnf());
+    }
+
+    #[test]
+    fn cnf_keeps_empty_start() {
+        let grammar: Grammar = "<list> ::= \"x\" <list> | \"\"".parse().unwrap();
+        let cnf = grammar.to_cnf();
+
+        assert!(cnf.is_cnf(), "{}", cnf);
+        assert_eq!(
+            cnf.to_string(),
+            "<list-start> ::= \"\" | <x> <list> | \"x\"
+<list> ::= <x> <list> | \"x\"
+<x> ::= \"x\"
+"
+        );
+        assert_same_language(&grammar, &cnf, &["", "x", "xxx", "y"]);
+    }
+
+    #[test]
+    fn cnf_keeps_declared_start() {
+        let grammar: Grammar = "%start <list>
+            <item> ::= \"x\"
+            <list> ::= <item> <list> | \"\""
+            .parse()
+            .unwrap();
+        let cnf = grammar.to_cnf();
+
+        assert!(cnf.is_cnf(), "{}", cnf);
+        assert_eq!(cnf.start_symbol(), Some("list-start"));
+        assert_eq!(
+            cnf.to_string(),
+            "%start <list-start>
+<list-start> ::= \"\" | <item> <list> | \"x\"
+<list> ::= <item> <list> | \"x\"
+<item> ::= \"x\"
+"
+        );
+        assert_same_language(&grammar, &cnf, &["", "x", "xxx", "y"]);
+    }
+
+    #[test]
+    fn cnf_long_expressions() {
+        let grammar: Grammar = "<s> ::= \"a\" \"b\" \"c\" \"d\"".parse().unwrap();
+        let cnf = grammar.to_cnf();
+
+        assert_eq!(
+            cnf.to_string(),
+            "<s> ::= <a> <s-rest>
+<s-rest> ::= <b> <s-rest-2>
+<s-rest-2> ::= <c> <d>
+<a> ::= \"a\"
+<b> ::= \"b\"
+<c> ::= \"c\"
+<d> ::= \"d\"
+"
+        );
+        assert!(cnf.is_cnf());
+    }
+
+    #[test]
+    fn cnf_verifier() {
+        let cases = [
+            (
+                "<s> ::= <a> <b> | \"x\"\n<a> ::= \"a\"\n<b> ::= \"b\"",
+                true,
+            ),
+            ("<s> ::= <s> <s> | \"x\"", true),
+            ("<s> ::= <s> <s> | \"x\" | \"\"", false),
+            ("<s> ::= <a> <a> | \"\"\n<a> ::= \"a\"", true),
+            ("<s> ::= <a>\n<a> ::= \"a\"", false),
+            ("<s> ::= <a> \"b\"\n<a> ::= \"a\"", false),
+            ("<s> ::= \"x\"\n<a> ::= \"\"", false),
+            ("<s> ::= <a> <a> <a>\n<a> ::= \"a\"", false),
+        ];
+        for &(grammar, expected) in &cases {
+            let grammar: Grammar = grammar.parse().unwrap();
+            assert_eq!(grammar.is_cnf(), expected, "{}", grammar);
+        }
+        assert!(Grammar::new().is_cnf());
+    }
+
+    fn is_gnf(grammar: &Grammar) -> bool {
+        grammar
+            .productions_iter()
+            .flat_map(|production| production.rhs_iter())
+            .all(|expression| match expression.terms_iter().next() {
+                Some(Term::Terminal(t)) => !t.is_empty(),
+                _ => false,
+            })
+    }
+
+    #[test]
+    fn gnf_conversion() {
+        let grammar: Grammar = "<expr> ::= <expr> \"+\" <term> | <term>
+            <term> ::= <term> \"*\" <factor> | <factor>
+            <factor> ::= \"(\" <expr> \")\" | \"x\""
+            .parse()
+            .unwrap();
+        let (gnf, derives_empty) = grammar.to_gnf();
+
+        assert!(!derives_empty);
+        assert!(is_gnf(&gnf), "{}", gnf);
+        assert_same_language(
+            &grammar,
+            &gnf,
+            &[
+                "x",
+                "x+x",
+                "(x)",
+                "x*(x+x)",
+                "(x*x)+x*x",
+                "",
+                "x+",
+                "()",
+                "x(x)",
+            ],
+        );
+    }
+
+    #[test]
+    fn gnf_indirect_recursion_and_empty() {
+        let grammar: Grammar = "<s> ::= <a> <b> | \"\"
+            <a> ::= <b> <s> | \"a\"
+            <b> ::= <s> <a> | \"b\" | <undefined> \"c\""
+            .parse()
+            .unwrap();
+        let (gnf, derives_empty) = grammar.to_gnf();
+
+        assert!(derives_empty);
+        assert!(is_gnf(&gnf), "{}", gnf);
+        assert!(gnf.parse_input("").is_err());
+        assert_same_language(
+            &grammar,
+            &gnf,
+            &[
+                "ab", "aa", "ba", "bb", "abab", "baab", "aab", "aaa", "b", "a", "abb", "c",
+            ],
+        );
+    }
+
+    #[test]
+    fn gnf_of_empty_language() {
+        let grammar: Grammar = "<s> ::= <s> \"a\"
+            <t> ::= \"b\""
+            .parse()
+            .unwrap();
+        let (gnf, derives_empty) = grammar.to_gnf();
+
+        assert!(!derives_empty);
+        assert_eq!(gnf.to_string(), "%start <s>\n<t> ::= \"b\"\n");
+        assert_eq!(gnf.to_string().parse(), Ok(gnf.clone()));
+        assert_same_language(&grammar, &gnf, &["", "a", "b", "ba"]);
+    }
+
+    #[test]
+    fn gnf_of_gnf() {
+        let grammar: Grammar = "<s> ::= \"a\" <s> <b> | \"a\"
+            <b> ::= \"b\""
+            .parse()
+            .unwrap();
+        let (gnf, derives_empty) = grammar.to_gnf();
+
+        assert!(!derives_empty);
+        assert_eq!(gnf, grammar);
+    }
+
+    #[test]
+    fn epsilon_removal() {
+        let grammar: Grammar = "<address> ::= <street> <opt-apt-num>
+            <street> ::= \"main\"
+            <opt-apt-num> ::= <apt-num> | \"\"
+            <apt-num> ::= \"1\" | \"2\""
+            .parse()
+            .unwrap();
+        let (removed, nullable_start) = grammar.remove_epsilon_productions();
+
+        assert!(!nullable_start);
+        assert_eq!(
+            removed.to_string(),
+            "<address> ::= <street> <opt-apt-num> | <street>
+<street> ::= \"main\"
+<opt-apt-num> ::= <apt-num>
+<apt-num> ::= \"1\" | \"2\"
+"
+        );
+        assert_same_language(&grammar, &removed, &["main", "main1", "main2", "1", ""]);
+    }
+
+    #[test]
+    fn epsilon_removal_nullable_start() {
+        let grammar: Grammar = "<s> ::= \"(\" <s> \")\" <s> | \"\"".parse().unwrap();
+        let (removed, nullable_start) = grammar.remove_epsilon_productions();
+
+        assert!(nullable_start);
+        assert_eq!(
+            removed.to_string(),
+            "<s-start> ::= <s> | \"\"
+<s> ::= \"(\" <s> \")\" <s> | \"(\" \")\" <s> | \"(\" <s> \")\" | \"(\" \")\"
+"
+        );
+        assert_same_language(&grammar, &removed, &["", "()", "(())()", "(()", ")("]);
+
+        let (_, nullable_start) = removed.remove_epsilon_productions();
+        assert!(nullable_start);
+    }
+
+    #[test]
+    fn epsilon_removal_drops_empty_nonterminals() {
+        let grammar: Grammar = "<s> ::= <a> \"x\"
+            <a> ::= \"\""
+            .parse()
+            .unwrap();
+        let (removed, nullable_start) = grammar.remove_epsilon_productions();
+
+        assert!(!nullable_start);
+        assert_eq!(removed.to_string(), "<s> ::= \"x\"\n");
+        assert_eq!(removed.to_string().parse(), Ok(removed.clone()));
+        assert_same_language(&grammar, &removed, &["", "x", "xx"]);
+    }
+
+    #[test]
+    fn unit_removal() {
+        let grammar: Grammar = "<s> ::= <a> | <b> \"x\"
+            <a> ::= <b> | \"a\"
+            <b> ::= <s> | \"b\" | \"\""
+            .parse()
+            .unwrap();
+        let removed = grammar.remove_unit_productions();
+
+        assert_eq!(
+            removed.to_string(),
+            "<s> ::= <b> \"x\" | \"a\" | \"b\" | \"\"
+<a> ::= \"a\" | \"b\" | \"\" | <b> \"x\"
+<b> ::= \"b\" | \"\" | <b> \"x\" | \"a\"
+"
+        );
+        assert_same_language(&grammar, &removed, &["", "a", "b", "x", "bx", "xx", "ax"]);
+    }
+
+    #[test]
+    fn unit_removal_drops_empty_nonterminals() {
+        let grammar: Grammar = "<s> ::= <u>".parse().unwrap();
+        let removed = grammar.remove_unit_productions();
+
+        assert_eq!(removed.to_string(), "%start <s>\n\n");
+        assert_eq!(removed.to_string().parse(), Ok(removed.clone()));
+        assert_same_language(&grammar, &removed, &["", "u"]);
+    }
+}
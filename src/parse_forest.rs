@@ -0,0 +1,304 @@
+use earley::{STACK_RED_ZONE, STACK_SEGMENT};
+use expression::Expression;
+use parse_tree::{ParseTree, ParseTreeNode};
+use production::Production;
+use stacker;
+use std::ops::Range;
+
+/// A child of a `PackedNode`: a matched terminal or a shared symbol node
+#[derive(Clone, Debug)]
+pub(crate) enum ForestChild<'gram> {
+    Terminal(&'gram str, Range<usize>),
+    Nonterminal(usize),
+}
+
+/// One way of deriving a symbol node: an `Expression` and a split of the
+/// symbol node's span across the expression's terms
+#[derive(Clone, Debug)]
+pub(crate) struct PackedNode<'gram> {
+    pub(crate) production: &'gram Production,
+    pub(crate) expression: &'gram Expression,
+    pub(crate) children: Vec<ForestChild<'gram>>,
+}
+
+/// Every derivation of one nonterminal over one span of input
+#[derive(Clone, Debug)]
+pub(crate) struct SymbolNode<'gram> {
+    pub(crate) span: Range<usize>,
+    pub(crate) packed: Vec<PackedNode<'gram>>,
+}
+
+/// A ParseForest holds every derivation of some input as a shared packed
+/// parse forest: each nonterminal over each span of input is stored once,
+/// no matter how many derivations share it.
+///
+/// Grammars with cycles, such as `<a> ::= <a> | "x"`, can derive an input
+/// in infinitely many ways. Those forests report no finite `count`, and
+/// `trees` yields only the trees which never derive a nonterminal over a
+/// span from itself.
+#[derive(Clone, Debug)]
+pub struct ParseForest<'gram> {
+    nodes: Vec<SymbolNode<'gram>>,
+}
+
+impl<'gram> ParseForest<'gram> {
+    /// Construct a `ParseForest` from symbol nodes, the first being the root
+    pub(crate) fn from_parts(nodes: Vec<SymbolNode<'gram>>) -> ParseForest<'gram> {
+        ParseForest { nodes }
+    }
+
+    /// Count the distinct parse trees without building them.
+    ///
+    /// Returns `None` if the forest contains a cycle, and so infinitely many
+    /// trees. Counts too large for a `usize` saturate at `usize::MAX`.
+    pub fn count(&self) -> Option<usize> {
+        let mut counts = vec![Count::Unvisited; self.nodes.len()];
+        self.count_node(0, &mut counts)
+    }
+
+    fn count_node(&self, node: usize, counts: &mut Vec<Count>) -> Option<usize> {
+        match counts[node] {
+            Count::Counted(count) => return Some(count),
+            Count::Counting => return None,
+            Count::Unvisited => counts[node] = Count::Counting,
+        }
+
+        let total = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+            let mut total: usize = 0;
+            for packed in &self.nodes[node].packed {
+                let mut product: usize = 1;
+                for child in &packed.children {
+                    if let ForestChild::Nonterminal(child) = *child {
+                        product = product.saturating_mul(self.count_node(child, counts)?);
+                    }
+                }
+                total = total.saturating_add(product);
+            }
+            Some(total)
+        })?;
+
+        counts[node] = Count::Counted(total);
+        Some(total)
+    }
+
+    /// Whether the input has more than one derivation
+    pub fn is_ambiguous(&self) -> bool {
+        self.count() != Some(1)
+    }
+
+    /// Get a lazy iterator of every distinct `ParseTree` in the forest
+    pub fn trees(&self) -> Trees<'_, 'gram> {
+        Trees {
+            forest: self,
+            choices: vec![],
+            done: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Count {
+    Unvisited,
+    Counting,
+    Counted(usize),
+}
+
+/// Iterator over the trees of a `ParseForest`.
+///
+/// Every symbol node visited while building a tree, in depth first order,
+/// picks one of its packed nodes. `choices` records those picks, and is
+/// advanced like an odometer between trees so that each combination of
+/// picks is visited exactly once.
+pub struct Trees<'forest, 'gram: 'forest> {
+    forest: &'forest ParseForest<'gram>,
+    choices: Vec<usize>,
+    done: bool,
+}
+
+struct Walk {
+    limits: Vec<usize>,
+    /// whether each symbol node is being built further up the tree
+    on_path: Vec<bool>,
+}
+
+impl<'forest, 'gram> Trees<'forest, 'gram> {
+    fn build(&mut self, node: usize, walk: &mut Walk) -> Option<ParseTree<'gram>> {
+        if walk.on_path[node] {
+            return None;
+        }
+
+        let symbol = &self.forest.nodes[node];
+        let position = walk.limits.len();
+        if position == self.choices.len() {
+            self.choices.push(0);
+        }
+        walk.limits.push(symbol.packed.len());
+
+        let packed = &symbol.packed[self.choices[position]];
+        walk.on_path[node] = true;
+
+        let rhs = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+            let mut rhs = vec![];
+            for child in &packed.children {
+                match *child {
+                    ForestChild::Terminal(t, ref span) => {
+                        rhs.push(ParseTreeNode::Terminal(t, span.clone()))
+                    }
+                    ForestChild::Nonterminal(child) => {
+                        rhs.push(ParseTreeNode::Nonterminal(self.build(child, walk)?))
+                    }
+                }
+            }
+            Some(rhs)
+        })?;
+
+        walk.on_path[node] = false;
+        Some(ParseTree::new(
+            packed.production,
+            packed.expression,
+            rhs,
+            symbol.span.clone(),
+        ))
+    }
+}
+
+impl<'forest, 'gram> Iterator for Trees<'forest, 'gram> {
+    type Item = ParseTree<'gram>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let mut walk = Walk {
+                limits: vec![],
+                on_path: vec![false; self.forest.nodes.len()],
+            };
+            let tree = self.build(0, &mut walk);
+
+            // later choices depend on earlier ones, so advance the last
+            // choice which has alternatives left and forget those after it
+            self.choices.truncate(walk.limits.len());
+            match (0..self.choices.len())
+                .rev()
+                .find(|&position| self.choices[position] + 1 < walk.limits[position])
+            {
+                Some(position) => {
+                    self.choices[position] += 1;
+                    self.choices.truncate(position + 1);
+                }
+                None => self.done = true,
+            }
+
+            if tree.is_some() {
+                return tree;
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grammar::Grammar;
+    use parse_tree::{ParseTree, ParseTreeNode};
+    use std::collections::HashSet;
+
+    fn leaves<'gram>(tree: &ParseTree<'gram>, into: &mut Vec<&'gram str>) {
+        for node in tree.rhs_iter() {
+            match *node {
+                ParseTreeNode::Terminal(t, _) => into.push(t),
+                ParseTreeNode::Nonterminal(ref child) => leaves(child, into),
+            }
+        }
+    }
+
+    #[test]
+    fn unambiguous() {
+        let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let forest = grammar.parse_forest("GATTACA").unwrap();
+
+        assert_eq!(forest.count(), Some(1));
+        assert!(!forest.is_ambiguous());
+        assert_eq!(forest.trees().count(), 1);
+    }
+
+    #[test]
+    fn catalan_ambiguity() {
+        let grammar: Grammar = "<e> ::= <e> \"+\" <e> | \"1\"".parse().unwrap();
+
+        // bracketings of n operands follow the Catalan numbers
+        for &(input, expected) in &[
+            ("1", 1),
+            ("1+1", 1),
+            ("1+1+1", 2),
+            ("1+1+1+1", 5),
+            ("1+1+1+1+1", 14),
+            ("1+1+1+1+1+1", 42),
+        ] {
+            let forest = grammar.parse_forest(input).unwrap();
+            assert_eq!(forest.count(), Some(expected), "{}", input);
+
+            let trees: Vec<_> = forest.trees().collect();
+            assert_eq!(trees.len(), expected, "{}", input);
+
+            let distinct: HashSet<_> = trees.iter().collect();
+            assert_eq!(distinct.len(), expected, "{}", input);
+
+            for tree in &trees {
+                let mut found = vec![];
+                leaves(tree, &mut found);
+                assert_eq!(found.concat(), input);
+            }
+        }
+    }
+
+    #[test]
+    fn ambiguous_alternatives() {
+        let grammar: Grammar = "<s> ::= <a> | <b>
+            <a> ::= \"x\" \"y\"
+            <b> ::= \"x\" <c>
+            <c> ::= \"y\""
+            .parse()
+            .unwrap();
+        let forest = grammar.parse_forest("xy").unwrap();
+
+        assert_eq!(forest.count(), Some(2));
+        assert!(forest.is_ambiguous());
+    }
+
+    #[test]
+    fn nullable_splits() {
+        let grammar: Grammar = "<s> ::= <opt> <opt> \"x\"
+            <opt> ::= \"\" | \"x\""
+            .parse()
+            .unwrap();
+
+        // either <opt> may be the one matching the first "x"
+        let forest = grammar.parse_forest("xx").unwrap();
+        assert_eq!(forest.count(), Some(2));
+        assert_eq!(forest.trees().count(), 2);
+    }
+
+    #[test]
+    fn cyclic() {
+        let grammar: Grammar = "<a> ::= <a> | \"x\"".parse().unwrap();
+        let forest = grammar.parse_forest("x").unwrap();
+
+        assert_eq!(forest.count(), None);
+        assert!(forest.is_ambiguous());
+        assert_eq!(forest.trees().count(), 1);
+    }
+
+    #[test]
+    fn lazy_trees() {
+        let grammar: Grammar = "<e> ::= <e> \"+\" <e> | \"1\"".parse().unwrap();
+        let input = vec!["1"; 30].join("+");
+        let forest = grammar.parse_forest(&input).unwrap();
+
+        // far more trees than could ever be built
+        assert_eq!(forest.count(), Some(1_002_242_216_651_368));
+        assert_eq!(forest.trees().take(3).count(), 3);
+    }
+}
@@ -0,0 +1,174 @@
+use expression::Expression;
+use production::Production;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+use std::slice;
+use term::Term;
+
+/// A ParseTreeNode is either a matched terminal or a nested `ParseTree`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ParseTreeNode<'gram> {
+    /// Terminal text and the byte range of input it matched
+    Terminal(&'gram str, Range<usize>),
+    Nonterminal(ParseTree<'gram>),
+}
+
+impl<'gram> ParseTreeNode<'gram> {
+    /// Get the byte range of input covered by the node
+    pub fn span(&self) -> Range<usize> {
+        match *self {
+            ParseTreeNode::Terminal(_, ref span) => span.clone(),
+            ParseTreeNode::Nonterminal(ref tree) => tree.span(),
+        }
+    }
+}
+
+/// A ParseTree is the derivation of a slice of input from one `Expression`
+/// of a `Production`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ParseTree<'gram> {
+    pub lhs: &'gram Term,
+    production: &'gram Production,
+    expression: &'gram Expression,
+    rhs: Vec<ParseTreeNode<'gram>>,
+    span: Range<usize>,
+}
+
+impl<'gram> ParseTree<'gram> {
+    pub(crate) fn new(
+        production: &'gram Production,
+        expression: &'gram Expression,
+        rhs: Vec<ParseTreeNode<'gram>>,
+        span: Range<usize>,
+    ) -> ParseTree<'gram> {
+        ParseTree {
+            lhs: &production.lhs,
+            production,
+            expression,
+            rhs,
+            span,
+        }
+    }
+
+    /// Get the `Production` this tree was derived from
+    pub fn production(&self) -> &'gram Production {
+        self.production
+    }
+
+    /// Get the `Expression` of the `Production` this tree was derived from
+    pub fn expression(&self) -> &'gram Expression {
+        self.expression
+    }
+
+    /// Get the byte range of input covered by the tree
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Get iterator of the tree's child nodes, one per `Term` of its `Expression`
+    pub fn rhs_iter(&self) -> Iter<'_, 'gram> {
+        Iter {
+            iterator: self.rhs.iter(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, prefix: &str) -> fmt::Result {
+        writeln!(f, "{} ::= {}", self.lhs, self.expression)?;
+
+        let count = self.rhs.len();
+        for (index, node) in self.rhs.iter().enumerate() {
+            let is_last = index + 1 == count;
+            let (branch, indent) = if is_last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            write!(f, "{}{}", prefix, branch)?;
+            match *node {
+                ParseTreeNode::Terminal(t, _) => writeln!(f, "{}", Term::Terminal(t.to_string()))?,
+                ParseTreeNode::Nonterminal(ref tree) => {
+                    tree.fmt_indented(f, &format!("{}{}", prefix, indent))?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'gram> Drop for ParseTree<'gram> {
+    // trees nest as deeply as the input is long, so unnest them into a
+    // worklist rather than dropping them recursively
+    fn drop(&mut self) {
+        let mut nested: Vec<ParseTree<'gram>> = vec![];
+        let mut rhs = mem::take(&mut self.rhs);
+        loop {
+            nested.extend(rhs.drain(..).filter_map(|node| match node {
+                ParseTreeNode::Nonterminal(tree) => Some(tree),
+                ParseTreeNode::Terminal(..) => None,
+            }));
+            match nested.pop() {
+                Some(mut tree) => rhs = mem::take(&mut tree.rhs),
+                None => break,
+            }
+        }
+    }
+}
+
+impl<'gram> fmt::Display for ParseTree<'gram> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, "")
+    }
+}
+
+pub struct Iter<'a, 'gram: 'a> {
+    iterator: slice::Iter<'a, ParseTreeNode<'gram>>,
+}
+
+impl<'a, 'gram> Iterator for Iter<'a, 'gram> {
+    type Item = &'a ParseTreeNode<'gram>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iterator.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grammar::Grammar;
+
+    #[test]
+    fn display_tree() {
+        let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let tree = grammar.parse_input("GA").unwrap();
+
+        let expected = [
+            "<dna> ::= <base> <dna>",
+            "├── <base> ::= \"G\"",
+            "│   └── \"G\"",
+            "└── <dna> ::= <base>",
+            "    └── <base> ::= \"A\"",
+            "        └── \"A\"",
+            "",
+        ]
+        .join("\n");
+
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn node_spans() {
+        let grammar: Grammar = "<pair> ::= <word> \" \" <word>
+            <word> ::= \"hello\" | \"world\""
+            .parse()
+            .unwrap();
+        let tree = grammar.parse_input("hello world").unwrap();
+        let spans: Vec<_> = tree.rhs_iter().map(|node| node.span()).collect();
+
+        assert_eq!(spans, vec![0..5, 5..6, 6..11]);
+    }
+}
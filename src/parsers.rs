@@ -1,17 +1,18 @@
 use expression::Expression;
 use grammar::Grammar;
 use production::Production;
+use span::{LineIndex, Span};
 use term::Term;
 
 use nom::{
     self,
     branch::alt,
-    bytes::complete::{tag, take_until},
+    bytes::complete::{tag, take_till, take_until},
     character::complete,
-    combinator::{all_consuming, complete, not, peek, recognize},
-    error::{ErrorKind, ParseError, VerboseError},
-    multi::many1,
-    sequence::{delimited, preceded, terminated},
+    combinator::{all_consuming, complete, cut, map, not, opt, peek, recognize},
+    error::{context, ErrorKind, ParseError, VerboseError, VerboseErrorKind},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, terminated},
     IResult, InputLength,
 };
 
@@ -24,146 +25,430 @@ pub fn eoi<I: Copy + InputLength, E: ParseError<I>>(input: I) -> IResult<I, I, E
     }
 }
 
-pub fn prod_lhs<'a>(input: &'a str) -> IResult<&'a str, Term, VerboseError<&'a str>> {
-    let (input, nt) = delimited(
-        complete::char('<'),
-        take_until(">"),
-        terminated(complete::char('>'), complete::multispace0),
-    )(input)?;
+/// A `#` comment to the end of the line or a `/* */` block comment,
+/// yielding the text between the delimiters
+pub fn comment(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((
+        preceded(complete::char('#'), take_till(|c| c == '\n' || c == '\r')),
+        delimited(tag("/*"), take_until("*/"), tag("*/")),
+    ))(input)
+}
+
+/// Any comments, with the whitespace around them, in source order
+pub fn comments(input: &str) -> IResult<&str, Vec<String>, VerboseError<&str>> {
+    preceded(
+        complete::multispace0,
+        many0(terminated(
+            map(comment, String::from),
+            complete::multispace0,
+        )),
+    )(input)
+}
+
+/// A comment which ends the line a production ends on, so belongs to it
+fn trailing_comment(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    terminated(
+        preceded(complete::space0, comment),
+        peek(preceded(
+            complete::space0,
+            alt((complete::line_ending, eoi)),
+        )),
+    )(input)
+}
+
+/// Skip any whitespace and comments
+pub fn comment_space(input: &str) -> IResult<&str, (), VerboseError<&str>> {
+    let (input, _) = many0(alt((complete::multispace1, comment)))(input)?;
+
+    Ok((input, ()))
+}
+
+/// Text up to an unescaped `close`, with escape sequences resolved.
+///
+/// Supports `\"`, `\'`, `\>`, `\\`, `\n`, `\t` and `\u{...}` with one
+/// to six hex digits; any other escape is a parse failure.
+fn escaped_until<'a>(
+    close: char,
+) -> impl Fn(&'a str) -> IResult<&'a str, String, VerboseError<&'a str>> {
+    move |input: &'a str| {
+        let mut text = String::new();
+        let mut chars = input.char_indices();
+        while let Some((index, c)) = chars.next() {
+            if c == close {
+                return Ok((&input[index..], text));
+            }
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+
+            let escaped = match chars.next() {
+                Some((_, 'n')) => Some('\n'),
+                Some((_, 't')) => Some('\t'),
+                Some((_, c)) if c == '\\' || c == '"' || c == '\'' || c == '>' => Some(c),
+                Some((_, 'u')) => {
+                    let rest = chars.as_str();
+                    let digits = rest
+                        .strip_prefix('{')
+                        .and_then(|rest| rest.find('}').map(|end| &rest[..end]));
+                    match digits {
+                        Some(digits)
+                            if !digits.is_empty()
+                                && digits.len() <= 6
+                                && digits.chars().all(|c| c.is_ascii_hexdigit()) =>
+                        {
+                            let c = u32::from_str_radix(digits, 16)
+                                .ok()
+                                .and_then(std::char::from_u32);
+                            // skip past the braces and digits
+                            for _ in 0..digits.len() + 2 {
+                                chars.next();
+                            }
+                            c
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            match escaped {
+                Some(c) => text.push(c),
+                None => {
+                    return Err(nom::Err::Failure(VerboseError::from_error_kind(
+                        &input[index..],
+                        ErrorKind::Escaped,
+                    )))
+                }
+            }
+        }
+
+        Err(nom::Err::Error(VerboseError::from_error_kind(
+            &input[input.len()..],
+            ErrorKind::TakeUntil,
+        )))
+    }
+}
+
+/// Text between `open` and `close` delimiters, with escape sequences resolved
+fn escaped<'a>(
+    open: char,
+    close: char,
+) -> impl Fn(&'a str) -> IResult<&'a str, String, VerboseError<&'a str>> {
+    let unclosed = match close {
+        '>' => "expected `>` to close nonterminal",
+        '"' => "expected `\"` to close terminal",
+        _ => "expected `'` to close terminal",
+    };
+
+    move |input: &'a str| {
+        let (rest, _) = complete::char(open)(input)?;
+        // once opened, no other alternative can make up for a missing close
+        let (rest, text) = escaped_until(close)(rest).map_err(|err| match err {
+            nom::Err::Error(e) => nom::Err::Failure(VerboseError::add_context(input, unclosed, e)),
+            err => err,
+        })?;
+        let (rest, _) = complete::char(close)(rest)?;
+
+        Ok((rest, text))
+    }
+}
+
+/// Run `parser`, also returning the provisional `Span` of what it consumed
+/// less any trailing whitespace
+fn spanned<'a, O, F>(
+    parser: F,
+) -> impl Fn(&'a str) -> IResult<&'a str, (O, Span), VerboseError<&'a str>>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>,
+{
+    move |input: &'a str| {
+        let (rest, output) = parser(input)?;
+        let consumed = input[..input.len() - rest.len()].trim_end();
+        let span = Span::remaining(input.len(), input.len() - consumed.len());
+
+        Ok((rest, (output, span)))
+    }
+}
+
+/// Collect the comments found between the terms of a production's right hand side
+fn inner_comments(rhs: &str) -> Vec<String> {
+    let skipped = alt((
+        map(escaped('"', '"'), |_| ()),
+        map(escaped('\'', '\''), |_| ()),
+        map(escaped('<', '>'), |_| ()),
+        map(complete::anychar, |_| ()),
+    ));
+    let result: IResult<&str, Vec<Option<&str>>, VerboseError<&str>> =
+        many0(alt((map(comment, Some), map(skipped, |_| None))))(rhs);
+
+    match result {
+        Ok((_, comments)) => comments.into_iter().flatten().map(String::from).collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[allow(clippy::needless_lifetimes)]
+pub fn prod_lhs<'a>(input: &'a str) -> IResult<&'a str, (Term, Span), VerboseError<&'a str>> {
+    let (input, (nt, span)) = spanned(escaped('<', '>'))(input)?;
 
     let (input, _) = preceded(
         complete::multispace0,
-        terminated(tag("::="), complete::multispace0),
+        terminated(context("expected `::=`", tag("::=")), complete::multispace0),
     )(input)?;
 
-    Ok((input, Term::Nonterminal(nt.to_string())))
+    Ok((input, (Term::Nonterminal(nt), span)))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn terminal<'a>(input: &'a str) -> IResult<&'a str, Term, VerboseError<&'a str>> {
-    let (input, t) = alt((
-        delimited(
-            complete::char('"'),
-            take_until("\""),
-            terminated(complete::char('"'), complete::multispace0),
-        ),
-        delimited(
-            complete::char('\''),
-            take_until("'"),
-            terminated(complete::char('\''), complete::multispace0),
-        ),
-    ))(input)?;
+    let (input, t) = terminated(
+        alt((escaped('"', '"'), escaped('\'', '\''))),
+        complete::multispace0,
+    )(input)?;
 
-    Ok((input, Term::Terminal(t.to_string())))
+    Ok((input, Term::Terminal(t)))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn nonterminal<'a>(input: &'a str) -> IResult<&'a str, Term, VerboseError<&'a str>> {
-    let (input, nt) = complete(delimited(
-        complete::char('<'),
-        take_until(">"),
-        terminated(complete::char('>'), complete::multispace0),
-    ))(input)?;
+    let (input, nt) = complete(terminated(escaped('<', '>'), complete::multispace0))(input)?;
 
     let (input, _) = preceded(
         complete::multispace0,
         not(complete(terminated(tag("::="), complete::multispace0))),
     )(input)?;
 
-    Ok((input, Term::Nonterminal(nt.to_string())))
+    Ok((input, Term::Nonterminal(nt)))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn term<'a>(input: &'a str) -> IResult<&'a str, Term, VerboseError<&'a str>> {
-    let (input, t) = alt((terminal, nonterminal))(input)?;
+    let (input, (t, _)) = spanned_term(input)?;
 
     Ok((input, t))
 }
 
+pub fn spanned_term(input: &str) -> IResult<&str, (Term, Span), VerboseError<&str>> {
+    preceded(comment_space, spanned(alt((terminal, nonterminal))))(input)
+}
+
+#[allow(clippy::needless_lifetimes)]
 pub fn term_complete<'a>(input: &'a str) -> IResult<&'a str, Term, VerboseError<&'a str>> {
     let (input, t) = all_consuming(term)(input)?;
 
     Ok((input, t))
 }
 
-pub fn expression_next<'a>(input: &'a str) -> IResult<&'a str, &str, VerboseError<&'a str>> {
-    let (input, _) = preceded(
-        complete::multispace0,
-        terminated(complete::char('|'), complete::multispace0),
-    )(input)?;
-
-    let (input, e) = recognize(peek(complete(expression)))(input)?;
-
-    Ok((input, e))
+#[allow(clippy::needless_lifetimes)]
+pub fn expression_next<'a>(input: &'a str) -> IResult<&'a str, &'a str, VerboseError<&'a str>> {
+    let (bar, _) = comment_space(input)?;
+    let (input, _) = terminated(complete::char('|'), complete::multispace0)(bar)?;
+
+    // nothing else may follow a `|`, so fail outright just after it, on
+    // the same line even when only a line break follows
+    match recognize(peek(complete(expression)))(input) {
+        Err(nom::Err::Error(_)) => Err(nom::Err::Failure(VerboseError {
+            errors: vec![(
+                &bar[1..],
+                VerboseErrorKind::Context("expected a term after `|`"),
+            )],
+        })),
+        result => result,
+    }
 }
 
 // delimited(complete::multispace0, tk, opt(complete::multispace1(input: T))
 
+#[allow(clippy::needless_lifetimes)]
 pub fn expression<'a>(input: &'a str) -> IResult<&'a str, Expression, VerboseError<&'a str>> {
     let (input, _) = peek(term)(input)?;
 
-    let (input, terms) = many1(complete(term))(input)?;
+    let (input, terms) = many1(complete(spanned_term))(input)?;
     let (input, _) = preceded(
         complete::multispace0,
         terminated(
-            alt((
-                recognize(peek(complete(eoi))),
-                recognize(peek(complete(complete::char(';')))),
-                expression_next,
-                recognize(peek(complete(prod_lhs))),
-            )),
+            context(
+                "expected a term, `|`, `;` or the next production",
+                alt((
+                    recognize(peek(complete(preceded(comment_space, eoi)))),
+                    recognize(peek(complete(preceded(comment_space, complete::char(';'))))),
+                    expression_next,
+                    recognize(peek(complete(preceded(comment_space, prod_lhs)))),
+                )),
+            ),
             complete::multispace0,
         ),
     )(input)?;
 
-    Ok((input, Expression::from_parts(terms)))
+    Ok((input, Expression::from_spanned_parts(terms)))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn expression_complete<'a>(
     input: &'a str,
 ) -> IResult<&'a str, Expression, VerboseError<&'a str>> {
-    let (input, e) = all_consuming(expression)(input)?;
+    let (rest, mut e) = all_consuming(expression)(input)?;
+    e.locate(&LineIndex::new(input));
 
-    Ok((input, e))
+    Ok((rest, e))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn production<'a>(input: &'a str) -> IResult<&'a str, Production, VerboseError<&'a str>> {
-    let (input, lhs) = preceded(
-        complete::multispace0,
-        terminated(prod_lhs, complete::multispace0),
-    )(input)?;
+    let (input, mut leading) = comments(input)?;
+    let (input, lhs) = terminated(prod_lhs, complete::multispace0)(input)?;
+    let rhs_start = input;
     let (input, rhs) = many1(complete(expression))(input)?;
-    let (input, _) = preceded(
-        complete::multispace0,
-        terminated(
-            alt((
-                recognize(peek(complete(eoi))),
-                tag(";"),
-                recognize(peek(complete(prod_lhs))),
-            )),
-            complete::multispace0,
-        ),
-    )(input)?;
+    let (input, _) = alt((
+        recognize(peek(complete(preceded(comment_space, eoi)))),
+        preceded(comment_space, tag(";")),
+        recognize(peek(complete(preceded(comment_space, prod_lhs)))),
+    ))(input)?;
+    let consumed = &rhs_start[..rhs_start.len() - input.len()];
+    leading.extend(inner_comments(consumed));
+    let same_line = !consumed
+        .trim_end_matches([' ', '\t'])
+        .ends_with(['\n', '\r']);
+    let (input, trailing) = match same_line {
+        true => opt(trailing_comment)(input)?,
+        false => (input, None),
+    };
+    leading.extend(trailing.map(String::from));
+    let (input, _) = complete::multispace0(input)?;
+
+    let mut production = Production::from_spanned_parts(lhs, rhs);
+    for comment in leading {
+        production.add_comment(comment);
+    }
 
-    Ok((input, Production::from_parts(lhs, rhs)))
+    Ok((input, production))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn production_complete<'a>(
     input: &'a str,
 ) -> IResult<&'a str, Production, VerboseError<&'a str>> {
-    let (input, p) = all_consuming(production)(input)?;
+    let (rest, (mut p, trailing)) = all_consuming(pair(production, comments))(input)?;
+    p.locate(&LineIndex::new(input));
+    for comment in trailing {
+        p.add_comment(comment);
+    }
+
+    Ok((rest, p))
+}
+
+/// A `%start <name>` directive declaring the start nonterminal, along with
+/// any comments ahead of it
+pub fn start_directive(input: &str) -> IResult<&str, (Vec<String>, String), VerboseError<&str>> {
+    let (input, comments) = comments(input)?;
+    let (input, _) = terminated(tag("%start"), complete::multispace0)(input)?;
+    let (input, start) = terminated(
+        context(
+            "expected a nonterminal after `%start`",
+            cut(escaped('<', '>')),
+        ),
+        complete::multispace0,
+    )(input)?;
 
-    Ok((input, p))
+    Ok((input, (comments, start)))
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn grammar<'a>(input: &'a str) -> IResult<&'a str, Grammar, VerboseError<&'a str>> {
-    let (input, _) = peek(production)(input)?;
-    let (input, prods) = many1(complete(production))(input)?;
+    let (input, directive) = opt(start_directive)(input)?;
+    // with a declared start no productions are needed, deriving nothing
+    let (input, mut prods) = match directive {
+        Some(_) => many0(complete(production))(input)?,
+        None => {
+            let (input, _) = peek(production)(input)?;
+            many1(complete(production))(input)?
+        }
+    };
+    let (input, trailing) = comments(input)?;
+
+    // comments after the last production stay with it
+    if let Some(last) = prods.last_mut() {
+        for comment in trailing {
+            last.add_comment(comment);
+        }
+    }
+
+    let start = directive.map(|(comments, start)| {
+        if let Some(first) = prods.first_mut() {
+            first.prepend_comments(comments);
+        }
+        start
+    });
+    let mut grammar = Grammar::from_parts(prods);
+    grammar.set_start_symbol(start);
+    Ok((input, grammar))
+}
+
+/// Quoted text on a single line, taken as is without resolving escapes
+fn raw_quoted<'a>(
+    quote: char,
+) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, VerboseError<&'a str>> {
+    move |input: &'a str| {
+        recognize(delimited(
+            complete::char(quote),
+            take_till(move |c| c == quote || c == '\n'),
+            complete::char(quote),
+        ))(input)
+    }
+}
+
+/// Skip past the start of a malformed production to the next `;` or
+/// `<name> ::=`, from where parsing may resume
+pub fn resynchronize(input: &str) -> &str {
+    let (input, _) = comment_space(input).unwrap_or((input, ()));
+    let mut chars = input.chars();
+    chars.next();
+
+    let mut rest = chars.as_str();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(';') {
+            return after;
+        }
+        if peek(prod_lhs)(rest).is_ok() {
+            return rest;
+        }
+
+        // step over whole terminals, so nothing inside them is mistaken for a
+        // boundary, including those on one line with malformed escapes
+        let terminal = |rest| {
+            recognize(escaped('"', '"'))(rest)
+                .or_else(|_| recognize(escaped('\'', '\''))(rest))
+                .or_else(|_| raw_quoted('"')(rest))
+                .or_else(|_| raw_quoted('\'')(rest))
+        };
+        rest = match terminal(rest) {
+            Ok((after, _)) => after,
+            Err(_) => {
+                let mut chars = rest.chars();
+                chars.next();
+                chars.as_str()
+            }
+        };
+    }
 
-    Ok((input, Grammar::from_parts(prods)))
+    rest
 }
 
+#[allow(clippy::needless_lifetimes)]
 pub fn grammar_complete<'a>(input: &'a str) -> IResult<&'a str, Grammar, VerboseError<&'a str>> {
-    let (input, g) = all_consuming(grammar)(input)?;
+    let (rest, mut g) = grammar(input)?;
+    if !rest.is_empty() {
+        // the text left over is not a production, so report why
+        production(rest)?;
+    }
+    let (rest, _) = eoi(rest)?;
+    let lines = LineIndex::new(input);
+    for production in g.productions_iter_mut() {
+        production.locate(&lines);
+    }
 
-    Ok((input, g))
+    Ok((rest, g))
 }
 
 #[cfg(test)]
@@ -265,4 +550,106 @@ mod tests {
             grammar(grammar_tuple.1.as_str()).unwrap().1
         );
     }
+
+    #[test]
+    fn comments_between_productions() {
+        let (_, parsed) = grammar(
+            "# digits
+            <digit> ::= \"0\" | \"1\" ; /* pairs
+            of digits */ <pair> ::= <digit> <digit>
+            # trailing",
+        )
+        .unwrap();
+        let comments: Vec<_> = parsed
+            .productions_iter()
+            .map(|production| production.comments().to_vec())
+            .collect();
+
+        assert_eq!(
+            comments,
+            vec![
+                vec![String::from(" digits")],
+                vec![
+                    String::from(" pairs\n            of digits "),
+                    String::from(" trailing")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_between_terms() {
+        let (_, parsed) = production(
+            "<a> ::= \"#\" /* first */ <b> # second
+                | \"/*\" <c>",
+        )
+        .unwrap();
+
+        assert_eq!(parsed, construct_commentless_production());
+        assert_eq!(
+            parsed.comments(),
+            &[String::from(" first "), String::from(" second")]
+        );
+    }
+
+    fn construct_commentless_production() -> Production {
+        Production::from_parts(
+            Term::Nonterminal(String::from("a")),
+            vec![
+                Expression::from_parts(vec![
+                    Term::Terminal(String::from("#")),
+                    Term::Nonterminal(String::from("b")),
+                ]),
+                Expression::from_parts(vec![
+                    Term::Terminal(String::from("/*")),
+                    Term::Nonterminal(String::from("c")),
+                ]),
+            ],
+        )
+    }
+
+    #[test]
+    fn grammar_spans() {
+        let source = "# bases
+<dna> ::= <base>
+        | <base> <dna> ;
+<base> ::= \"A\" | 'ç' | \"T\"";
+        let (_, parsed) = grammar_complete(source).unwrap();
+        let productions: Vec<_> = parsed.productions_iter().collect();
+        let at = |span: Option<Span>| {
+            let span = span.unwrap();
+            (
+                (span.start.line, span.start.column),
+                (span.end.line, span.end.column),
+                &source[span.start.offset..span.end.offset],
+            )
+        };
+
+        assert_eq!(at(productions[0].lhs_span()), ((2, 1), (2, 6), "<dna>"));
+        assert_eq!(
+            at(productions[0].span()),
+            ((2, 1), (3, 23), "<dna> ::= <base>\n        | <base> <dna>")
+        );
+
+        let second = productions[0].rhs_iter().nth(1).unwrap();
+        assert_eq!(at(second.span()), ((3, 11), (3, 23), "<base> <dna>"));
+        assert_eq!(at(second.term_span(1)), ((3, 18), (3, 23), "<dna>"));
+        assert_eq!(second.term_span(2), None);
+
+        let base = productions[1].rhs_iter().nth(1).unwrap();
+        assert_eq!(at(base.term_span(0)), ((4, 18), (4, 21), "'ç'"));
+        assert_eq!(
+            at(productions[1].span()),
+            ((4, 1), (4, 27), "<base> ::= \"A\" | 'ç' | \"T\"")
+        );
+    }
+
+    #[test]
+    fn expression_spans() {
+        let (_, parsed) = expression_complete("  <a> /* b */ \"c\"  ").unwrap();
+        let span = parsed.span().unwrap();
+
+        assert_eq!((span.start.offset, span.end.offset), (2, 17));
+        assert_eq!(parsed.term_span(1).unwrap().start.offset, 14);
+    }
 }
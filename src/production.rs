@@ -1,18 +1,34 @@
 #![allow(clippy::should_implement_trait)]
 
-use error::Error;
+use error::{Error, ParseError};
 use expression::Expression;
 use parsers;
+use span::{LineIndex, Span};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::slice;
 use std::str::FromStr;
 use term::Term;
 
 /// A Production is comprised of any number of Expressions
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+///
+/// Comments carried by a `Production` are printed ahead of it by `Display`,
+/// and productions parsed from text remember their source spans, but neither
+/// takes part in comparing or hashing productions.
+///
+/// Parsing attaches to a production the comments ahead of it, those between
+/// its terms, and one ending the line it ends on. Printing normalises them
+/// all to the front, so a round trip keeps every comment with its
+/// production, though not in its original place. A comment holding both a
+/// line break and `*/` fits in no block comment, so it prints, and reparses,
+/// as one `#` comment per line.
+#[derive(Clone, Debug)]
 pub struct Production {
     pub lhs: Term,
     rhs: Vec<Expression>,
+    comments: Vec<String>,
+    span: Option<Span>,
+    lhs_span: Option<Span>,
 }
 
 impl Production {
@@ -21,12 +37,95 @@ impl Production {
         Production {
             lhs: Term::Nonterminal(String::new()),
             rhs: vec![],
+            comments: vec![],
+            span: None,
+            lhs_span: None,
         }
     }
 
     /// Construct an `Production` from `Expression`s
     pub fn from_parts(t: Term, e: Vec<Expression>) -> Production {
-        Production { lhs: t, rhs: e }
+        Production {
+            lhs: t,
+            rhs: e,
+            comments: vec![],
+            span: None,
+            lhs_span: None,
+        }
+    }
+
+    /// Construct a `Production` from a parsed left hand side and its provisional span
+    pub(crate) fn from_spanned_parts(lhs: (Term, Span), rhs: Vec<Expression>) -> Production {
+        let end = rhs
+            .last()
+            .and_then(|expression| expression.span())
+            .map_or(lhs.1.end, |span| span.end);
+
+        Production {
+            lhs: lhs.0,
+            rhs,
+            comments: vec![],
+            span: Some(Span {
+                start: lhs.1.start,
+                end,
+            }),
+            lhs_span: Some(lhs.1),
+        }
+    }
+
+    /// Resolve provisional spans against the source they were parsed from
+    pub(crate) fn locate(&mut self, lines: &LineIndex) {
+        for span in self.span.iter_mut().chain(self.lhs_span.iter_mut()) {
+            span.locate(lines);
+        }
+        for expression in &mut self.rhs {
+            expression.locate(lines);
+        }
+    }
+
+    /// Get the source span of the `Production`, from its left hand side to the
+    /// end of its last `Expression`, if it was parsed from text
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate bnf;
+    /// use bnf::Grammar;
+    ///
+    /// fn main() {
+    ///     let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+    ///     <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+    ///         .parse()
+    ///         .unwrap();
+    ///     let base = grammar.productions_iter().nth(1).unwrap();
+    ///     let span = base.span().unwrap();
+    ///
+    ///     assert_eq!((span.start.line, span.start.column), (2, 5));
+    /// }
+    /// ```
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Get the source span of the `Production`'s left hand side, if it was parsed from text
+    pub fn lhs_span(&self) -> Option<Span> {
+        self.lhs_span
+    }
+
+    /// Attach a comment to the `Production`, without its `#` or `/* */` delimiters
+    pub fn add_comment(&mut self, comment: String) {
+        self.comments.push(comment)
+    }
+
+    /// Attach `comments` ahead of those the `Production` already has
+    pub(crate) fn prepend_comments(&mut self, mut comments: Vec<String>) {
+        comments.append(&mut self.comments);
+        self.comments = comments;
+    }
+
+    /// Get the comments attached to the `Production`, in source order
+    pub fn comments(&self) -> &[String] {
+        &self.comments
     }
 
     /// Add `Expression` to the `Production`'s right hand side
@@ -46,6 +145,7 @@ impl Production {
     }
 
     /// Get iterator of the `Production`'s right hand side `Expression`s
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     pub fn rhs_iter(&self) -> Iter {
         Iter {
             iterator: self.rhs.iter(),
@@ -53,6 +153,7 @@ impl Production {
     }
 
     /// Get mutable iterator of the `Production`'s right hand side `Expression`s
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     pub fn rhs_iter_mut(&mut self) -> IterMut {
         IterMut {
             iterator: self.rhs.iter_mut(),
@@ -75,8 +176,38 @@ impl Default for Production {
     }
 }
 
+impl PartialEq for Production {
+    fn eq(&self, other: &Production) -> bool {
+        self.lhs == other.lhs && self.rhs == other.rhs
+    }
+}
+
+impl Eq for Production {}
+
+impl Hash for Production {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lhs.hash(state);
+        self.rhs.hash(state);
+    }
+}
+
 impl fmt::Display for Production {
+    #[allow(clippy::to_string_in_format_args)]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for comment in &self.comments {
+            let multiline = comment.contains('\n') || comment.contains('\r');
+            if !multiline {
+                writeln!(f, "#{}", comment)?;
+            } else if !comment.contains("*/") {
+                writeln!(f, "/*{}*/", comment)?;
+            } else {
+                // no block comment can hold this one, so print it line by line
+                for line in comment.replace("\r\n", "\n").split(['\n', '\r']) {
+                    writeln!(f, "#{}", line)?;
+                }
+            }
+        }
+
         write!(
             f,
             "{} ::= {}",
@@ -95,7 +226,7 @@ impl FromStr for Production {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match parsers::production_complete(s) {
             Result::Ok((_, o)) => Ok(o),
-            Result::Err(e) => Err(Error::from(e)),
+            Result::Err(e) => Err(Error::from(ParseError::from_nom(s, e))),
         }
     }
 }
@@ -134,18 +265,13 @@ mod tests {
 
     impl Arbitrary for Production {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
-            let lhs_str = String::arbitrary(g)
-                .chars()
-                .filter(|&c| (c != '>'))
-                .collect();
-
-            let lhs = Term::Nonterminal(lhs_str);
+            let lhs = Term::Nonterminal(String::arbitrary(g));
 
             let mut rhs = Vec::<Expression>::arbitrary(g);
             if rhs.is_empty() {
                 rhs.push(Expression::arbitrary(g));
             }
-            Production { lhs, rhs }
+            Production::from_parts(lhs, rhs)
         }
     }
 
@@ -289,6 +415,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn comments_round_trip() {
+        let source = "# line comment
+/* block
+comment */
+<dna> ::= <base> | <base> <dna>";
+        let production = Production::from_str(source).unwrap();
+        assert_eq!(
+            production.comments(),
+            &[
+                String::from(" line comment"),
+                String::from(" block\ncomment ")
+            ]
+        );
+
+        let reparsed = Production::from_str(&production.to_string()).unwrap();
+        assert_eq!(reparsed.comments(), production.comments());
+        assert_eq!(reparsed.to_string(), production.to_string());
+    }
+
+    #[test]
+    fn comments_ignored_by_eq() {
+        let mut commented = Production::from_str("<a> ::= \"x\"").unwrap();
+        commented.add_comment(String::from(" explanation"));
+
+        assert_eq!(commented, Production::from_str("<a> ::= \"x\"").unwrap());
+        assert_eq!(commented.to_string(), "# explanation\n<a> ::= \"x\"");
+    }
+
     #[test]
     fn parse_semicolon_separated() {
         let result = Production::from_str("<base> ::= \"A\" ; \"C\" ; \"G\" ; \"T\"");
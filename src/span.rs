@@ -0,0 +1,123 @@
+use std::fmt;
+
+/// A Position is a place in source text, as a 1-based line and column
+/// (counted in characters) alongside its byte offset
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A Span is the region of source text a `Term`, `Expression` or
+/// `Production` was parsed from, `end` being exclusive
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// Construct a provisional `Span` from the lengths of input remaining
+    /// at its start and end, to be resolved against the source by `locate`
+    pub(crate) fn remaining(start: usize, end: usize) -> Span {
+        let position = |remaining| Position {
+            line: 0,
+            column: 0,
+            offset: remaining,
+        };
+        Span {
+            start: position(start),
+            end: position(end),
+        }
+    }
+
+    /// Resolve a provisional `Span` made by `remaining`
+    pub(crate) fn locate(&mut self, lines: &LineIndex) {
+        self.start = lines.position(lines.source.len() - self.start.offset);
+        self.end = lines.position(lines.source.len() - self.end.offset);
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Finds the line and column of byte offsets into source text
+pub(crate) struct LineIndex<'a> {
+    source: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(source: &'a str) -> LineIndex<'a> {
+        let starts = Some(0)
+            .into_iter()
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        LineIndex { source, starts }
+    }
+
+    pub(crate) fn position(&self, offset: usize) -> Position {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let line_start = self.starts[line];
+
+        Position {
+            line: line + 1,
+            column: self.source[line_start..offset].chars().count() + 1,
+            offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let lines = LineIndex::new("ab\nçd\n\nx");
+
+        let expected = [
+            (0, 1, 1),
+            (1, 1, 2),
+            (3, 2, 1),
+            (5, 2, 2),
+            (7, 3, 1),
+            (8, 4, 1),
+            (9, 4, 2),
+        ];
+        for &(offset, line, column) in &expected {
+            assert_eq!(
+                lines.position(offset),
+                Position {
+                    line,
+                    column,
+                    offset
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn locate_remaining() {
+        let source = "<a> ::= \"x\"\n<b> ::= <a>";
+        let mut span = Span::remaining(source.len() - 12, 0);
+        span.locate(&LineIndex::new(source));
+
+        assert_eq!(span.to_string(), "2:1-2:12");
+        assert_eq!(span.start.offset, 12);
+        assert_eq!(span.end.offset, source.len());
+    }
+}
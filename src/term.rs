@@ -1,6 +1,6 @@
 #![allow(clippy::should_implement_trait)]
 
-use error::Error;
+use error::{Error, ParseError};
 use parsers;
 use std::fmt;
 use std::str::FromStr;
@@ -17,22 +17,40 @@ impl FromStr for Term {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match parsers::term_complete(s) {
             Result::Ok((_, o)) => Ok(o),
-            Result::Err(e) => Err(Error::from(e)),
+            Result::Err(e) => Err(Error::from(ParseError::from_nom(s, e))),
         }
     }
 }
 
+/// Escape backslashes, the closing `delimiter`, newlines and tabs
+fn escape(s: &str, delimiter: char) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c == delimiter => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Term::Terminal(ref s) => {
-                if s.contains('"') {
-                    write!(f, "'{}'", s)
+                if s.contains('"') && !s.contains('\'') {
+                    write!(f, "'{}'", escape(s, '\''))
                 } else {
-                    write!(f, "\"{}\"", s)
+                    write!(f, "\"{}\"", escape(s, '"'))
                 }
             }
-            Term::Nonterminal(ref s) => write!(f, "<{}>", s),
+            Term::Nonterminal(ref s) => write!(f, "<{}>", escape(s, '>')),
         }
     }
 }
@@ -46,16 +64,10 @@ mod tests {
 
     impl Arbitrary for Term {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
-            let mut term = String::arbitrary(g);
+            let term = String::arbitrary(g);
             if bool::arbitrary(g) {
-                term = term.chars().filter(|&c| (c != '>')).collect();
                 Term::Nonterminal(term)
             } else {
-                if term.contains('"') {
-                    term = term.chars().filter(|&c| c != '\'').collect();
-                } else if term.contains('\'') {
-                    term = term.chars().filter(|&c| c != '"').collect();
-                }
                 Term::Terminal(term)
             }
         }
@@ -90,7 +102,13 @@ mod tests {
 
         let error = incomplete.unwrap_err();
         match error {
-            Error::ParseError(ref s) => assert!(s.starts_with("Parsing error:")),
+            Error::ParseError(ref e) => {
+                assert_eq!(
+                    e.expected,
+                    "expected `>` to close nonterminal started at 1:1"
+                );
+                assert_eq!(e.position.column, 5);
+            }
             _ => panic!("Incomplete term should be parse error"),
         }
     }
@@ -154,4 +172,46 @@ mod tests {
         let from_string = Term::from_str(&to_string);
         assert_eq!(Ok(Term::Terminal(String::from("\""))), from_string);
     }
+
+    #[test]
+    fn parse_escapes() {
+        assert_eq!(
+            Term::from_str(r#""both \" and ' \\ \n\t\u{1F600}""#),
+            Ok(Term::Terminal(String::from(
+                "both \" and ' \\ \n\t\u{1F600}"
+            )))
+        );
+        assert_eq!(
+            Term::from_str(r"<a \> b>"),
+            Ok(Term::Nonterminal(String::from("a > b")))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_escapes() {
+        for invalid in &[
+            r#""\q""#,
+            r#""\u{}""#,
+            r#""\u{110000}""#,
+            r#""\u41""#,
+            r#""\u{+41}""#,
+        ] {
+            match Term::from_str(invalid) {
+                Err(Error::ParseError(_)) => (),
+                e => panic!("{} should be Error::ParseError: {:?}", invalid, e),
+            }
+        }
+    }
+
+    #[test]
+    fn display_escapes() {
+        let both = Term::Terminal(String::from("\"'\\\n\t"));
+        assert_eq!(both.to_string(), r#""\"'\\\n\t""#);
+
+        let double_quote = Term::Terminal(String::from("say \"hi\""));
+        assert_eq!(double_quote.to_string(), "'say \"hi\"'");
+
+        let nonterminal = Term::Nonterminal(String::from("a>b"));
+        assert_eq!(nonterminal.to_string(), r"<a\>b>");
+    }
 }
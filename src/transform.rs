@@ -0,0 +1,511 @@
+//! Transforms producing a new `Grammar` for the same language.
+//!
+//! Transforms work on `Rules`, which group every nonterminal's alternatives
+//! under one name as plain sequences of terms. Empty terminals `""` are
+//! dropped from those sequences, so an empty alternative is an empty
+//! sequence, and written back as `""`. The start nonterminal always comes
+//! first, so a declared start symbol moves its production to the front.
+
+use expression::Expression;
+use fresh::FreshNames;
+use grammar::Grammar;
+use production::Production;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use term::Term;
+
+/// An alternative of a nonterminal, without any empty terminals
+pub(crate) type Alternative = Vec<Term>;
+
+/// A `Grammar`'s productions grouped by nonterminal
+pub(crate) struct Rules {
+    /// Nonterminals in order of their first production, the start first
+    pub(crate) names: Vec<String>,
+    /// The grammar's declared start symbol, kept when it is replaced
+    pub(crate) declared_start: Option<String>,
+    pub(crate) alternatives: HashMap<String, Vec<Alternative>>,
+    comments: HashMap<String, Vec<String>>,
+    /// Productions with a terminal lhs, passed through untouched
+    terminal_lhs: Vec<Production>,
+    pub(crate) fresh: FreshNames,
+}
+
+impl Rules {
+    pub(crate) fn new(grammar: &Grammar) -> Rules {
+        let mut rules = Rules {
+            names: vec![],
+            declared_start: grammar.declared_start().map(String::from),
+            alternatives: HashMap::new(),
+            comments: HashMap::new(),
+            terminal_lhs: vec![],
+            fresh: FreshNames::new(Vec::new()),
+        };
+        let mut used = vec![];
+
+        for production in grammar.productions_iter() {
+            let lhs = match production.lhs {
+                Term::Nonterminal(ref nt) => nt,
+                Term::Terminal(_) => {
+                    rules.terminal_lhs.push(production.clone());
+                    continue;
+                }
+            };
+            if !rules.alternatives.contains_key(lhs) {
+                rules.names.push(lhs.clone());
+            }
+            rules
+                .comments
+                .entry(lhs.clone())
+                .or_default()
+                .extend(production.comments().iter().cloned());
+
+            let alternatives = rules.alternatives.entry(lhs.clone()).or_default();
+            for expression in production.rhs_iter() {
+                let alternative: Alternative = expression
+                    .terms_iter()
+                    .filter(|term| !is_empty_terminal(term))
+                    .cloned()
+                    .collect();
+                used.extend(alternative.iter().filter_map(|term| match *term {
+                    Term::Nonterminal(ref nt) => Some(nt.clone()),
+                    Term::Terminal(_) => None,
+                }));
+                alternatives.push(alternative);
+            }
+        }
+
+        if let Some(ref start) = rules.declared_start {
+            if let Some(index) = rules.names.iter().position(|name| name == start) {
+                let start = rules.names.remove(index);
+                rules.names.insert(0, start);
+            }
+        }
+
+        used.extend(rules.names.iter().cloned());
+        used.extend(rules.declared_start.iter().cloned());
+        rules.fresh = FreshNames::new(used);
+        rules
+    }
+
+    /// Add `name` with `alternatives` straight after `after`, or last
+    pub(crate) fn insert(
+        &mut self,
+        name: String,
+        alternatives: Vec<Alternative>,
+        after: Option<&str>,
+    ) {
+        let index = after
+            .and_then(|after| self.names.iter().position(|n| n == after))
+            .map_or(self.names.len(), |index| index + 1);
+        self.names.insert(index, name.clone());
+        self.alternatives.insert(name, alternatives);
+    }
+
+    /// Remove nonterminals left without alternatives, which no text can
+    /// define, along with every alternative using them. A removed start
+    /// nonterminal becomes the declared start, so it still derives nothing
+    /// rather than the next nonterminal taking its place.
+    pub(crate) fn remove_empty_nonterminals(&mut self) {
+        let start = match self.declared_start {
+            Some(_) => None,
+            None => self.names.first().cloned(),
+        };
+
+        loop {
+            let empty: HashSet<String> = self
+                .names
+                .iter()
+                .filter(|name| self.alternatives.get(*name).map_or(true, Vec::is_empty))
+                .cloned()
+                .collect();
+            if empty.is_empty() {
+                break;
+            }
+
+            self.names.retain(|name| !empty.contains(name));
+            for name in &empty {
+                self.alternatives.remove(name);
+                self.comments.remove(name);
+            }
+            for alternatives in self.alternatives.values_mut() {
+                alternatives.retain(|alternative| {
+                    !alternative.iter().any(|term| match *term {
+                        Term::Nonterminal(ref nt) => empty.contains(nt),
+                        Term::Terminal(_) => false,
+                    })
+                });
+            }
+        }
+
+        if let Some(start) = start {
+            if !self.names.contains(&start) {
+                self.declared_start = Some(start);
+            }
+        }
+    }
+
+    pub(crate) fn into_grammar(mut self) -> Grammar {
+        let mut productions = vec![];
+        for name in &self.names {
+            let expressions = self
+                .alternatives
+                .remove(name)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|alternative| {
+                    if alternative.is_empty() {
+                        Expression::from_parts(vec![Term::Terminal(String::new())])
+                    } else {
+                        Expression::from_parts(alternative)
+                    }
+                })
+                .collect();
+            let mut production =
+                Production::from_parts(Term::Nonterminal(name.clone()), expressions);
+            for comment in self.comments.remove(name).unwrap_or_default() {
+                production.add_comment(comment);
+            }
+            productions.push(production);
+        }
+        productions.extend(self.terminal_lhs);
+        let mut grammar = Grammar::from_parts(productions);
+        grammar.set_start_symbol(self.declared_start);
+        grammar
+    }
+}
+
+fn is_empty_terminal(term: &Term) -> bool {
+    match *term {
+        Term::Terminal(ref t) => t.is_empty(),
+        Term::Nonterminal(_) => false,
+    }
+}
+
+/// Push `alternative` unless it is already present
+pub(crate) fn push_unique(alternatives: &mut Vec<Alternative>, alternative: Alternative) {
+    if !alternatives.contains(&alternative) {
+        alternatives.push(alternative);
+    }
+}
+
+/// Remove direct and indirect left recursion, returning the new grammar and
+/// a map from each new tail nonterminal to the nonterminal it came from
+///
+/// Nonterminals are ordered by their first production. Each one's
+/// alternatives starting with an earlier nonterminal are expanded, then its
+/// direct left recursion `<a> ::= <a> α | β` becomes `<a> ::= β <a-tail>`
+/// and `<a-tail> ::= α <a-tail> | ""`. Alternatives `<a> ::= <a>` derive
+/// nothing new and are dropped, as are nonterminals left with no
+/// alternatives and the alternatives using them. Left recursion hidden behind a nullable
+/// nonterminal, as in `<a> ::= <b> <a>` with nullable `<b>`, is not removed.
+pub(crate) fn eliminate_left_recursion(grammar: &Grammar) -> (Grammar, BTreeMap<String, String>) {
+    let mut rules = Rules::new(grammar);
+    let mut origins = BTreeMap::new();
+    let names = rules.names.clone();
+
+    for (i, name) in names.iter().enumerate() {
+        let mut alternatives = rules.alternatives[name].clone();
+        for earlier in &names[..i] {
+            let earlier_term = Term::Nonterminal(earlier.clone());
+            let mut expanded = vec![];
+            for alternative in alternatives {
+                if alternative.first() == Some(&earlier_term) {
+                    for prefix in &rules.alternatives[earlier] {
+                        let mut replaced = prefix.clone();
+                        replaced.extend(alternative[1..].iter().cloned());
+                        push_unique(&mut expanded, replaced);
+                    }
+                } else {
+                    push_unique(&mut expanded, alternative);
+                }
+            }
+            alternatives = expanded;
+        }
+
+        let term = Term::Nonterminal(name.clone());
+        let (recursive, others): (Vec<_>, Vec<_>) = alternatives
+            .into_iter()
+            .partition(|alternative| alternative.first() == Some(&term));
+        let recursive: Vec<Alternative> = recursive
+            .into_iter()
+            .map(|alternative| alternative[1..].to_vec())
+            .filter(|rest| !rest.is_empty())
+            .collect();
+
+        if recursive.is_empty() {
+            rules.alternatives.insert(name.clone(), others);
+            continue;
+        }
+
+        let tail = rules.fresh.fresh(&format!("{}-tail", name));
+        let tail_term = Term::Nonterminal(tail.clone());
+        let with_tail = |mut alternative: Alternative| {
+            alternative.push(tail_term.clone());
+            alternative
+        };
+        let mut tail_alternatives: Vec<Alternative> =
+            recursive.into_iter().map(&with_tail).collect();
+        tail_alternatives.push(vec![]);
+
+        rules
+            .alternatives
+            .insert(name.clone(), others.into_iter().map(&with_tail).collect());
+        rules.insert(tail.clone(), tail_alternatives, Some(name));
+        origins.insert(tail, name.clone());
+    }
+
+    rules.remove_empty_nonterminals();
+    (rules.into_grammar(), origins)
+}
+
+/// Left factor every nonterminal, so no two of its alternatives start with
+/// the same term
+///
+/// Alternatives sharing a first term are replaced by their longest common
+/// prefix followed by a fresh `{name}-suffix` nonterminal, whose
+/// alternatives are the remaining suffixes. Suffix nonterminals are factored
+/// in turn, until no prefixes are shared. Duplicate alternatives are merged.
+pub(crate) fn left_factor(grammar: &Grammar) -> Grammar {
+    let mut rules = Rules::new(grammar);
+    let mut pending: VecDeque<(String, String)> = rules
+        .names
+        .iter()
+        .map(|name| (name.clone(), name.clone()))
+        .collect();
+
+    while let Some((name, base)) = pending.pop_front() {
+        let mut groups: Vec<Vec<Alternative>> = vec![];
+        for alternative in rules.alternatives[&name].clone() {
+            let group = groups
+                .iter_mut()
+                .find(|group| !alternative.is_empty() && group[0].first() == alternative.first());
+            match group {
+                Some(group) => {
+                    if !group.contains(&alternative) {
+                        group.push(alternative);
+                    }
+                }
+                None => groups.push(vec![alternative]),
+            }
+        }
+
+        let mut after = name.clone();
+        let mut alternatives = vec![];
+        for group in groups {
+            // empty alternatives are each a group of their own
+            if group.len() == 1 {
+                for alternative in group {
+                    push_unique(&mut alternatives, alternative);
+                }
+                continue;
+            }
+
+            let prefix_len = (1..group[0].len())
+                .take_while(|&i| group.iter().all(|alt| alt.get(i) == group[0].get(i)))
+                .count()
+                + 1;
+            let suffix = rules.fresh.fresh(&format!("{}-suffix", base));
+            let mut factored = group[0][..prefix_len].to_vec();
+            factored.push(Term::Nonterminal(suffix.clone()));
+            alternatives.push(factored);
+
+            let suffixes = group
+                .into_iter()
+                .map(|alternative| alternative[prefix_len..].to_vec())
+                .collect();
+            rules.insert(suffix.clone(), suffixes, Some(&after));
+            pending.push_back((suffix.clone(), base.clone()));
+            after = suffix;
+        }
+        rules.alternatives.insert(name, alternatives);
+    }
+
+    rules.into_grammar()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_language(original: &Grammar, transformed: &Grammar, sentences: &[&str]) {
+        for sentence in sentences {
+            assert_eq!(
+                original.parse_input(sentence).is_ok(),
+                transformed.parse_input(sentence).is_ok(),
+                "{:?} in\n{}",
+                sentence,
+                transformed
+            );
+        }
+    }
+
+    #[test]
+    fn direct_left_recursion() {
+        let grammar: Grammar = "<expr> ::= <expr> \"+\" <term> | <expr> \"-\" <term> | <term>
+            <term> ::= <term> \"*\" \"x\" | \"x\""
+            .parse()
+            .unwrap();
+        let (transformed, origins) = grammar.eliminate_left_recursion();
+
+        assert_eq!(
+            transformed.to_string(),
+            "<expr> ::= <term> <expr-tail>
+<expr-tail> ::= \"+\" <term> <expr-tail> | \"-\" <term> <expr-tail> | \"\"
+<term> ::= \"x\" <term-tail>
+<term-tail> ::= \"*\" \"x\" <term-tail> | \"\"
+"
+        );
+        assert_eq!(origins.len(), 2);
+        assert_eq!(origins["expr-tail"], "expr");
+        assert_eq!(origins["term-tail"], "term");
+        assert_same_language(
+            &grammar,
+            &transformed,
+            &["x", "x+x", "x-x*x", "x*x*x+x", "", "+x", "x+", "x**x"],
+        );
+    }
+
+    #[test]
+    fn indirect_left_recursion() {
+        let grammar: Grammar = "<s> ::= <a> \"a\" | \"b\"
+            <a> ::= <a> \"c\" | <s> \"d\" | \"\""
+            .parse()
+            .unwrap();
+        let (transformed, origins) = grammar.eliminate_left_recursion();
+
+        assert_eq!(
+            transformed.to_string(),
+            "<s> ::= <a> \"a\" | \"b\"
+<a> ::= \"b\" \"d\" <a-tail> | <a-tail>
+<a-tail> ::= \"c\" <a-tail> | \"a\" \"d\" <a-tail> | \"\"
+"
+        );
+        assert_eq!(origins["a-tail"], "a");
+        assert_same_language(
+            &grammar,
+            &transformed,
+            &["a", "b", "bda", "ca", "adca", "bdcadca", "ad", "bd", "cc"],
+        );
+    }
+
+    #[test]
+    fn fresh_names_and_cycles() {
+        let grammar: Grammar = "<a> ::= <a> | <a> \"x\" | <a-tail>
+            <a-tail> ::= \"y\""
+            .parse()
+            .unwrap();
+        let (transformed, origins) = grammar.eliminate_left_recursion();
+
+        assert_eq!(
+            transformed.to_string(),
+            "<a> ::= <a-tail> <a-tail-2>
+<a-tail-2> ::= \"x\" <a-tail-2> | \"\"
+<a-tail> ::= \"y\"
+"
+        );
+        assert_eq!(origins["a-tail-2"], "a");
+    }
+
+    #[test]
+    fn drops_nonterminals_without_alternatives() {
+        let grammar: Grammar = "<s> ::= <t> \"x\" | \"y\"
+            <t> ::= <t>"
+            .parse()
+            .unwrap();
+        let (transformed, _) = grammar.eliminate_left_recursion();
+
+        assert_eq!(transformed.to_string(), "<s> ::= \"y\"\n");
+        assert_eq!(transformed.to_string().parse(), Ok(transformed));
+
+        let grammar: Grammar = "<t> ::= <t>".parse().unwrap();
+        let (transformed, _) = grammar.eliminate_left_recursion();
+
+        assert_eq!(transformed.to_string(), "%start <t>\n\n");
+        assert_eq!(transformed.to_string().parse(), Ok(transformed));
+    }
+
+    #[test]
+    fn keeps_grammars_without_left_recursion() {
+        let grammar: Grammar = "# bases
+            <dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let (transformed, origins) = grammar.eliminate_left_recursion();
+
+        assert_eq!(transformed, grammar);
+        assert_eq!(
+            transformed.productions_iter().next().unwrap().comments(),
+            &[" bases"]
+        );
+        assert!(origins.is_empty());
+    }
+
+    #[test]
+    fn left_factoring() {
+        let grammar: Grammar =
+            "<if> ::= \"if\" <c> \"then\" <s> | \"if\" <c> \"then\" <s> \"else\" <s>
+            <c> ::= \"c\"
+            <s> ::= \"s\" | <if>"
+                .parse()
+                .unwrap();
+        let factored = grammar.left_factor();
+
+        assert_eq!(
+            factored.to_string(),
+            "<if> ::= \"if\" <c> \"then\" <s> <if-suffix>
+<if-suffix> ::= \"\" | \"else\" <s>
+<c> ::= \"c\"
+<s> ::= \"s\" | <if>
+"
+        );
+        assert_same_language(
+            &grammar,
+            &factored,
+            &[
+                "ifcthens",
+                "ifcthenselses",
+                "ifcthenifcthenselses",
+                "ifcthen",
+                "ifcthenselse",
+            ],
+        );
+    }
+
+    #[test]
+    fn left_factoring_iterates() {
+        let grammar: Grammar =
+            "<a> ::= \"x\" \"y\" | \"x\" \"z\" \"1\" | \"b\" | \"x\" \"z\" \"2\" | \"b\" | \"x\""
+                .parse()
+                .unwrap();
+        let factored = grammar.left_factor();
+
+        assert_eq!(
+            factored.to_string(),
+            "<a> ::= \"x\" <a-suffix> | \"b\"
+<a-suffix> ::= \"y\" | \"z\" <a-suffix-2> | \"\"
+<a-suffix-2> ::= \"1\" | \"2\"
+"
+        );
+        assert_same_language(
+            &grammar,
+            &factored,
+            &["xy", "xz1", "xz2", "b", "x", "xz", "bb", ""],
+        );
+        assert_eq!(factored.left_factor(), factored);
+    }
+
+    #[test]
+    fn left_factoring_merges_empty_alternatives() {
+        let grammar: Grammar = "<s> ::= \"x\" | \"\" | \"\"".parse().unwrap();
+        let factored = grammar.left_factor();
+
+        assert_eq!(factored.to_string(), "<s> ::= \"x\" | \"\"\n");
+    }
+
+    #[test]
+    fn left_factoring_keeps_factored_grammars() {
+        let grammar: Grammar = "<a> ::= \"x\" <a> | \"y\" | \"\"".parse().unwrap();
+
+        assert_eq!(grammar.left_factor(), grammar);
+    }
+}
@@ -0,0 +1,211 @@
+use grammar::Grammar;
+use std::collections::HashSet;
+use std::fmt;
+use term::Term;
+
+/// A ValidationReport lists the problems `Grammar::validate` found, each in
+/// the order it first appears in the grammar
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValidationReport {
+    /// Nonterminals used on a right hand side, or declared the start symbol,
+    /// without any production defining them
+    pub undefined: Vec<String>,
+    /// Left hand side nonterminals no derivation from the start symbol can reach
+    pub unreachable: Vec<String>,
+    /// Text of `Term::Terminal`s used as a production's left hand side
+    pub terminal_lhs: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Whether no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.undefined.is_empty() && self.unreachable.is_empty() && self.terminal_lhs.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "grammar is valid");
+        }
+
+        let problems = self
+            .undefined
+            .iter()
+            .map(|nt| format!("undefined nonterminal {}", Term::Nonterminal(nt.clone())))
+            .chain(self.unreachable.iter().map(|nt| {
+                format!(
+                    "unreachable production for {}",
+                    Term::Nonterminal(nt.clone())
+                )
+            }))
+            .chain(self.terminal_lhs.iter().map(|t| {
+                format!(
+                    "terminal {} used as left hand side",
+                    Term::Terminal(t.clone())
+                )
+            }))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", problems.join("\n"))
+    }
+}
+
+/// Push `item` onto `list` unless it was seen before
+fn push_once(list: &mut Vec<String>, seen: &mut HashSet<String>, item: &str) {
+    if seen.insert(item.to_string()) {
+        list.push(item.to_string());
+    }
+}
+
+/// The nonterminals `start` can derive, including itself
+pub(crate) fn reachable<'a>(grammar: &'a Grammar, start: &'a Term) -> HashSet<&'a Term> {
+    let mut reached: HashSet<&Term> = HashSet::new();
+    let mut pending = vec![start];
+    while let Some(lhs) = pending.pop() {
+        if !reached.insert(lhs) {
+            continue;
+        }
+        for production in grammar.productions_iter().filter(|p| p.lhs == *lhs) {
+            for term in production
+                .rhs_iter()
+                .flat_map(|expression| expression.terms_iter())
+            {
+                if let Term::Nonterminal(_) = *term {
+                    pending.push(term);
+                }
+            }
+        }
+    }
+    reached
+}
+
+pub(crate) fn validate(grammar: &Grammar) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let defined: HashSet<&str> = grammar
+        .productions_iter()
+        .filter_map(|production| match production.lhs {
+            Term::Nonterminal(ref nt) => Some(nt.as_str()),
+            Term::Terminal(_) => None,
+        })
+        .collect();
+
+    let mut seen = HashSet::new();
+    if let Some(start) = grammar.declared_start() {
+        if !defined.contains(start) {
+            push_once(&mut report.undefined, &mut seen, start);
+        }
+    }
+
+    let mut seen_terminals = HashSet::new();
+    for production in grammar.productions_iter() {
+        if let Term::Terminal(ref t) = production.lhs {
+            push_once(&mut report.terminal_lhs, &mut seen_terminals, t);
+        }
+        for term in production
+            .rhs_iter()
+            .flat_map(|expression| expression.terms_iter())
+        {
+            if let Term::Nonterminal(ref nt) = *term {
+                if !defined.contains(nt.as_str()) {
+                    push_once(&mut report.undefined, &mut seen, nt);
+                }
+            }
+        }
+    }
+
+    let start = match grammar.start_symbol() {
+        Some(start) => Term::Nonterminal(start.to_string()),
+        None => return report,
+    };
+    let reached = reachable(grammar, &start);
+
+    let mut seen = HashSet::new();
+    for production in grammar.productions_iter() {
+        if let Term::Nonterminal(ref nt) = production.lhs {
+            if !reached.contains(&production.lhs) {
+                push_once(&mut report.unreachable, &mut seen, nt);
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expression::Expression;
+    use production::Production;
+
+    #[test]
+    fn valid_grammar() {
+        let grammar: Grammar = "<dna> ::= <base> | <base> <dna>
+            <base> ::= \"A\" | \"C\" | \"G\" | \"T\""
+            .parse()
+            .unwrap();
+        let report = grammar.validate();
+
+        assert!(report.is_valid());
+        assert_eq!(report.to_string(), "grammar is valid");
+    }
+
+    #[test]
+    fn finds_problems() {
+        let mut grammar: Grammar = "<start> ::= <a> <not-used> | <b>
+            <a> ::= \"a\" | <missing>
+            <b> ::= <not-used>
+            <orphan> ::= <island>
+            <island> ::= <orphan> | <missing>
+            <orphan> ::= \"o\""
+            .parse()
+            .unwrap();
+        grammar.add_production(Production::from_parts(
+            Term::Terminal(String::from("t")),
+            vec![Expression::from_parts(vec![Term::Nonterminal(
+                String::from("a"),
+            )])],
+        ));
+        let report = grammar.validate();
+
+        assert_eq!(
+            report,
+            ValidationReport {
+                undefined: vec![String::from("not-used"), String::from("missing")],
+                unreachable: vec![String::from("orphan"), String::from("island")],
+                terminal_lhs: vec![String::from("t")],
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "undefined nonterminal <not-used>
+undefined nonterminal <missing>
+unreachable production for <orphan>
+unreachable production for <island>
+terminal \"t\" used as left hand side"
+        );
+    }
+
+    #[test]
+    fn undefined_start() {
+        let grammar: Grammar = "%start <road>
+            <a> ::= \"x\" <road>"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            grammar.validate(),
+            ValidationReport {
+                undefined: vec![String::from("road")],
+                unreachable: vec![String::from("a")],
+                terminal_lhs: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn empty_grammar() {
+        assert!(Grammar::new().validate().is_valid());
+    }
+}
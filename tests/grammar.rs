@@ -28,7 +28,7 @@ const BNF_FOR_BNF: &str = "
         <literal>        ::= '\"' <text1> '\"' | \"'\" <text2> \"'\"
         <text1>          ::= \"\" | <character1> <text1>
         <text2>          ::= \"\" | <character2> <text2>
-        <character>      ::= <letter> | <digit> | <symbol>
+        <character>      ::= <letter> | <digit> | <symbol> | <escape>
         <letter>         ::= \"A\" | \"B\" | \"C\" | \"D\" | \"E\" | \"F\"
                             | \"G\" | \"H\" | \"I\" | \"J\" | \"K\" | \"L\"
                             | \"M\" | \"N\" | \"O\" | \"P\" | \"Q\" | \"R\"
@@ -44,8 +44,11 @@ const BNF_FOR_BNF: &str = "
                             | \"%\" | \"&\" | \"(\" | \")\" | \"*\" | \"+\"
                             | \",\" | \"-\" | \".\" | \"/\" | \":\" | \";\"
                             |\">\" | \"=\" | \"<\" | \"?\" | \"@\" | \"[\"
-                            | \"\\\" | \"]\" | \"^\" | \"_\" | \"`\"
+                            | \"]\" | \"^\" | \"_\" | \"`\"
                             | \"{{\" | \"}}\" | \"~\"
+        <escape>         ::= \"\\\\\" <escaped>
+        <escaped>        ::= \"\\\\\" | \"n\" | \"t\" | '\"' | \"'\" | \">\"
+                            | \"u{41}\" | \"u{1F600}\"
         <character1>     ::= <character> | \"'\"
         <character2>     ::= <character> | '\"'
         <rule-name>      ::= <letter> | <rule-name> <rule-char>
@@ -0,0 +1,74 @@
+extern crate bnf;
+
+use bnf::{Error, Grammar, ParseTreeNode, Term};
+
+const POSTAL_ADDRESS: &str = "
+    <postal-address> ::= <name-part> <street-address> <zip-part>
+    <name-part> ::= <first-name> \" \" <last-name> <EOL>
+    <street-address> ::= <house-num> \" \" <street-name> <opt-apt-num> <EOL>
+    <zip-part> ::= <town-name> \", \" <state-code> \" \" <ZIP-code> <EOL>
+    <opt-apt-num> ::= \" #\" <house-num> | \"\"
+    <first-name> ::= \"Ada\" | \"Alan\"
+    <last-name> ::= \"Lovelace\" | \"Turing\"
+    <house-num> ::= <digit> | <digit> <house-num>
+    <digit> ::= \"0\" | \"1\" | \"2\" | \"3\" | \"4\" | \"5\" | \"6\" | \"7\" | \"8\" | \"9\"
+    <street-name> ::= \"Main St\" | \"Elm St\"
+    <town-name> ::= \"Springfield\"
+    <state-code> ::= \"IL\" | \"OR\"
+    <ZIP-code> ::= <digit> <digit> <digit> <digit> <digit>
+    <EOL> ::= \"\n\"";
+
+#[test]
+fn parse_postal_address() {
+    let grammar: Grammar = POSTAL_ADDRESS.parse().unwrap();
+    let input = "Ada Lovelace\n1815 Main St #12\nSpringfield, IL 62701\n";
+    let tree = grammar.parse_input(input).unwrap();
+
+    assert_eq!(tree.span(), 0..input.len());
+    assert_eq!(tree.lhs, &Term::Nonterminal(String::from("postal-address")));
+
+    let children: Vec<&str> = tree
+        .rhs_iter()
+        .map(|node| match *node {
+            ParseTreeNode::Nonterminal(ref child) => &input[child.span()],
+            ParseTreeNode::Terminal(t, _) => t,
+        })
+        .collect();
+    assert_eq!(
+        children,
+        vec![
+            "Ada Lovelace\n",
+            "1815 Main St #12\n",
+            "Springfield, IL 62701\n"
+        ]
+    );
+}
+
+#[test]
+fn parse_sub_rule() {
+    let grammar: Grammar = POSTAL_ADDRESS.parse().unwrap();
+    let tree = grammar
+        .parse_input_from("street-address", "7 Elm St\n")
+        .unwrap();
+
+    assert_eq!(tree.lhs, &Term::Nonterminal(String::from("street-address")));
+}
+
+#[test]
+fn parse_rejects_invalid_input() {
+    let grammar: Grammar = POSTAL_ADDRESS.parse().unwrap();
+    let result = grammar.parse_input("Ada Lovelace\n1815 Main St #\n");
+
+    match result {
+        Err(Error::ParseInputError(_)) => (),
+        e => panic!("should be Error::ParseInputError: {:?}", e),
+    }
+}
+
+#[test]
+fn parse_missing_start() {
+    let grammar: Grammar = POSTAL_ADDRESS.parse().unwrap();
+    let result = grammar.parse_input_from("country", "");
+
+    assert!(result.is_err(), "{:?} should be error", result);
+}